@@ -0,0 +1,231 @@
+use enclose::enclose;
+use rexie::Rexie;
+use std::cell::Cell;
+use std::rc::Rc;
+use web_sys::{DragEvent, Event, HtmlInputElement, MouseEvent};
+use yew::{html, AttrValue, Callback, Component, Context, Html, Properties, TargetCast};
+
+use crate::notify::{Notification, Notification::Warning};
+use crate::utils::backup::{
+    create_library_archive, prepare_library_archive, restore_library_archive, PreparedRestore,
+};
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub db: Rc<Rexie>,
+    pub notify: Callback<Notification>,
+    pub close_modal: Callback<MouseEvent>,
+}
+
+pub enum Message {
+    Backup,
+    Progress(usize, usize),
+    Cancel,
+    Cancelled,
+    Set(gloo_file::File),
+    Prepare(gloo_file::File),
+    Restore(Box<PreparedRestore>),
+    Restored(usize),
+    Notify(Notification),
+}
+
+enum State {
+    Default,
+    /// (volumes written, total volumes) and the flag used to cancel the backup.
+    BackingUp(Option<(usize, usize)>, Rc<Cell<bool>>),
+    Ready(File),
+    /// (volumes restored, total volumes)
+    Restoring(Option<(usize, usize)>),
+    Restored(usize),
+}
+
+struct File {
+    _url_object: gloo_file::ObjectUrl,
+    file: gloo_file::File,
+    url: AttrValue,
+}
+
+/// BackupModal creates a modal overlay where users can back up their entire library
+/// (every volume and the global settings) into a single archive, or replace their
+/// library with the contents of such an archive.
+pub struct BackupModal {
+    state: State,
+    backup: Callback<MouseEvent>,
+    cancel: Callback<MouseEvent>,
+    cancel_click: Callback<MouseEvent>,
+    cancel_drag: Callback<DragEvent>,
+    onchange: Callback<Event>,
+    ondrop: Callback<DragEvent>,
+}
+
+impl Component for BackupModal {
+    type Message = Message;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let backup = ctx.link().callback(|_| Message::Backup);
+        let cancel = ctx.link().callback(|_| Message::Cancel);
+        let cancel_click = Callback::from(|e: MouseEvent| e.stop_propagation());
+        let cancel_drag = Callback::from(|e: DragEvent| e.prevent_default());
+        let onchange = ctx.link().batch_callback(|e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let file = input.files().and_then(|files| files.item(0));
+            input.set_value("");
+            file.map(|file| Message::Prepare(file.into()))
+        });
+        let ondrop = ctx.link().batch_callback(|e: DragEvent| {
+            e.prevent_default();
+            let file = e.data_transfer().and_then(|data| data.files()).and_then(|files| files.item(0));
+            file.map(|file| Message::Prepare(file.into()))
+        });
+        Self { state: State::Default, backup, cancel, cancel_click, cancel_drag, onchange, ondrop }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let Props { db, notify, .. } = ctx.props();
+        match msg {
+            Message::Backup => {
+                let cancelled = Rc::new(Cell::new(false));
+                let progress = ctx.link().callback(|(n, m)| Message::Progress(n, m));
+                self.state = State::BackingUp(None, cancelled.clone());
+                ctx.link().send_future(enclose!((db) backup(db, progress, cancelled)));
+                true
+            }
+            Message::Progress(n, m) => {
+                match &mut self.state {
+                    State::BackingUp(progress, _) | State::Restoring(progress) => *progress = Some((n, m)),
+                    _ => return false,
+                }
+                true
+            }
+            Message::Cancel => {
+                if let State::BackingUp(_, cancelled) = &self.state {
+                    cancelled.set(true);
+                    self.state = State::Default;
+                    return true;
+                }
+                false
+            }
+            Message::Cancelled => false,
+            Message::Set(file) => {
+                let _url_object = gloo_file::ObjectUrl::from(file.clone());
+                let url = AttrValue::from(_url_object.to_string());
+                self.state = State::Ready(File { _url_object, file, url });
+                true
+            }
+            Message::Prepare(file) => {
+                if matches!(self.state, State::BackingUp(..) | State::Restoring(_)) { return false; }
+                self.state = State::Restoring(None);
+                ctx.link().send_future(prepare(file));
+                true
+            }
+            Message::Restore(prepared) => {
+                let prompt = format!(
+                    "Restoring this backup (created {}) will replace your entire library with \
+                     its {} volume(s), and replace your settings.\nThere is no undo!",
+                    prepared.created_at(), prepared.volume_count(),
+                );
+                if !gloo_dialogs::confirm(&prompt) {
+                    self.state = State::Default;
+                    return true;
+                }
+                let progress = ctx.link().callback(|(n, m)| Message::Progress(n, m));
+                ctx.link().send_future(enclose!((db) restore(db, *prepared, progress)));
+                false
+            }
+            Message::Restored(count) => {
+                self.state = State::Restored(count);
+                true
+            }
+            Message::Notify(notification) => {
+                if let State::BackingUp(..) | State::Restoring(_) = self.state {
+                    self.state = State::Default;
+                }
+                notify.emit(notification);
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let Props { close_modal, .. } = ctx.props();
+        let (onchange, ondrop) = (&self.onchange, &self.ondrop);
+        let (ondragover, ondragenter) = (&self.cancel_drag, &self.cancel_drag);
+        let status = match &self.state {
+            State::Default => html! {
+                <button onclick={&self.backup}>{"Back Up Library"}</button>
+            },
+            State::BackingUp(progress, _) => {
+                let text = match progress {
+                    Some((n, m)) => format!("volume {n} / {m}"),
+                    None => "Preparing...".to_string(),
+                };
+                html! {<>
+                    <button>{text}</button>
+                    <button onclick={&self.cancel}>{"Cancel"}</button>
+                </>}
+            }
+            State::Ready(file) => html! {
+                <a href={&file.url} download={file.file.name()}>
+                    <button>{"Download Backup"}</button>
+                </a>
+            },
+            State::Restoring(progress) => {
+                let text = match progress {
+                    Some((n, m)) => format!("Restoring volume {n} / {m}..."),
+                    None => "Reading backup...".to_string(),
+                };
+                html! { <p>{text}</p> }
+            }
+            State::Restored(count) => html! {
+                <p>{format!("Restored {count} volume(s)")}</p>
+            },
+        };
+        html! {
+            <div id="Modal" onclick={close_modal}>
+                <div class="modal-content" onclick={&self.cancel_click}>
+                    <div class="close-symbol" onclick={close_modal}>{crate::icons::close()}</div>
+                    <p class="modal-title">{ "Back Up Your Library" }</p>
+                    <p class="modal-note">
+                        {"Your library only exists within this browser. "}
+                        {"A backup contains every volume (including your edits and reading progress) "}
+                        {"and your settings, and can be restored into any browser. "}
+                        {"Restoring a backup replaces your entire library."}
+                    </p>
+                    <div id="BackupStatus">{status}</div>
+                    <label for="backup-upload">
+                        <div id="drop-container" {ondrop} {ondragover} {ondragenter}>
+                            <p>{"drop a library backup here to restore it"}</p>
+                            <p>{"or"}</p>
+                            <p>{"click to browse"}</p>
+                        </div>
+                    </label>
+                    <input id="backup-upload" type="file" accept=".zip" {onchange}/>
+                </div>
+            </div>
+        }
+    }
+}
+
+async fn backup(db: Rc<Rexie>, progress: Callback<(usize, usize)>, cancelled: Rc<Cell<bool>>) -> Message {
+    match create_library_archive(db, progress, cancelled.clone()).await {
+        Ok(Some(file)) => Message::Set(file),
+        Ok(None) => Message::Cancelled,
+        Err(_) if cancelled.get() => Message::Cancelled,
+        Err(err) => Message::Notify(Warning("failed to create library backup", err.to_string())),
+    }
+}
+
+async fn prepare(file: gloo_file::File) -> Message {
+    match prepare_library_archive(file).await {
+        Ok(prepared) => Message::Restore(Box::new(prepared)),
+        Err(err) => Message::Notify(Warning("failed to read library backup", err.to_string())),
+    }
+}
+
+async fn restore(db: Rc<Rexie>, prepared: PreparedRestore, progress: Callback<(usize, usize)>) -> Message {
+    match restore_library_archive(&db, prepared, progress).await {
+        Ok(count) => Message::Restored(count),
+        Err(err) => Message::Notify(Warning("failed to restore library backup", err.to_string())),
+    }
+}
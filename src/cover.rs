@@ -0,0 +1,132 @@
+use enclose::enclose;
+use rexie::Rexie;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::{IntersectionObserver, IntersectionObserverEntry, IntersectionObserverInit};
+use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties};
+
+use crate::models::VolumeId;
+use crate::notify::{Notification, Notification::Warning};
+use crate::utils::db::get_page;
+use crate::utils::thumbnail::{load_page_thumbnail, load_thumbnail};
+
+/// How far outside the viewport images start loading, so they are ready when scrolled to.
+const ROOT_MARGIN: &str = "400px";
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub db: Rc<Rexie>,
+    pub notify: Callback<Notification>,
+    pub volume_id: VolumeId,
+    pub page: AttrValue,
+    pub alt: AttrValue,
+    /// Display the thumbnail of the volume instead of the page,
+    /// i.e. the page is the cover of the volume.
+    #[prop_or_default]
+    pub thumbnail: bool,
+    /// Display a downscaled copy of the page rather than the full resolution page.
+    #[prop_or_default]
+    pub small: bool,
+}
+
+pub enum Message {
+    Visible,
+    Set(gloo_file::ObjectUrl),
+    Notify(Notification),
+}
+
+type ObserverCallback = Closure<dyn FnMut(js_sys::Array, IntersectionObserver)>;
+
+/// Cover displays a page of a volume (usually its thumbnail), which is loaded
+/// from IndexedDB once the cover is (nearly) scrolled into view.
+pub struct Cover {
+    node: NodeRef,
+    observer: Option<(IntersectionObserver, ObserverCallback)>,
+    url: Option<(gloo_file::ObjectUrl, AttrValue)>,
+}
+
+impl Component for Cover {
+    type Message = Message;
+    type Properties = Props;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self { node: NodeRef::default(), observer: None, url: None }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Message::Visible => {
+                if let Some((observer, _)) = self.observer.take() {
+                    observer.disconnect();
+                    Self::load(ctx);
+                }
+                false
+            }
+            Message::Set(object_url) => {
+                let url = AttrValue::from(object_url.to_string());
+                self.url = Some((object_url, url));
+                true
+            }
+            Message::Notify(notification) => {
+                ctx.props().notify.emit(notification);
+                false
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let src = self.url.as_ref().map(|(_, url)| url.clone());
+        html! { <img ref={&self.node} {src} alt={&ctx.props().alt}/> }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if !first_render { return; }
+        let Some(element) = self.node.cast::<web_sys::Element>() else { return; };
+        let link = ctx.link().clone();
+        let callback: ObserverCallback = Closure::new(move |entries: js_sys::Array, _| {
+            let visible = entries.iter()
+                .any(|entry| entry.unchecked_into::<IntersectionObserverEntry>().is_intersecting());
+            if visible { link.send_message(Message::Visible); }
+        });
+        let options = IntersectionObserverInit::new();
+        options.set_root_margin(ROOT_MARGIN);
+        match IntersectionObserver::new_with_options(callback.as_ref().unchecked_ref(), &options) {
+            Ok(observer) => {
+                observer.observe(&element);
+                self.observer = Some((observer, callback));
+            }
+            // Without an observer, the cover is loaded straight away.
+            Err(_) => Self::load(ctx),
+        }
+    }
+
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        if let Some((observer, _)) = self.observer.take() {
+            observer.disconnect();
+        }
+    }
+}
+
+impl Cover {
+    fn load(ctx: &Context<Self>) {
+        let Props { db, volume_id, page, thumbnail, small, .. } = ctx.props();
+        ctx.link().send_future(enclose!((db, volume_id, page, thumbnail, small) {
+            fetch(db, volume_id, page, thumbnail, small)
+        }));
+    }
+}
+
+async fn fetch(db: Rc<Rexie>, volume_id: VolumeId, page: AttrValue, thumbnail: bool, small: bool) -> Message {
+    let image = if thumbnail {
+        load_thumbnail(&db, volume_id, page).await
+    } else if small {
+        load_page_thumbnail(&db, volume_id, page).await
+    } else {
+        get_page(db, volume_id, page).await
+    };
+    match image {
+        Ok(image) => Message::Set(image.into()),
+        Err(err) => Message::Notify(Warning("failed to load the cover of a volume", err.to_string())),
+    }
+}
@@ -3,24 +3,49 @@ pub type Result<T> = std::result::Result<T, AppError>;
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum AppError {
+    /// An error which occurred while reading or importing an entry of a zip archive.
+    ArchiveEntry(String, Box<AppError>),
     InvalidMokuroFile(InvalidMokuroFileError),
     GlooFileError(gloo_file::FileReadError),
     RexieError(rexie::Error),
     SerdeJsonError(serde_json::Error),
     SerdeWasmError(serde_wasm_bindgen::Error),
-    ZipError(zip::result::ZipError),
+    ZipError(ZipError),
     JsValueError(wasm_bindgen::JsValue),
 }
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub enum InvalidMokuroFileError {
-    MissingFile(String)
+    MissingFile(String),
+    MultipleVolumes(Vec<String>),
+    /// The archive was created by a newer version of this app.
+    UnsupportedVersion(u32),
+    UnknownFormat,
+}
+
+/// An error in the structure of a zip archive (see utils::blob_zip).
+#[derive(Debug)]
+pub enum ZipError {
+    InvalidArchive(&'static str),
+    UnsupportedArchive(&'static str),
+    Io(std::io::Error),
+}
+
+impl std::fmt::Display for ZipError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZipError::InvalidArchive(e) => write!(f, "invalid Zip archive: {}", e),
+            ZipError::UnsupportedArchive(e) => write!(f, "unsupported Zip archive: {}", e),
+            ZipError::Io(e) => write!(f, "i/o error: {}", e),
+        }
+    }
 }
 
 impl std::fmt::Display for AppError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            AppError::ArchiveEntry(entry, e) => write!(f, "{} (in {})", e, entry),
             AppError::InvalidMokuroFile(e) => write!(f, "Invalid mokuro file: {:?}", e),
             AppError::GlooFileError(e) => write!(f, "Gloo file error: {}", e),
             AppError::RexieError(e) => write!(f, "Rexie error: {}", e),
@@ -34,6 +59,17 @@ impl std::fmt::Display for AppError {
 
 impl std::error::Error for AppError {}
 
+impl AppError {
+    /// The name of the archive entry which caused the error, if known.
+    pub fn entry(&self) -> Option<&str> {
+        match self {
+            AppError::ArchiveEntry(entry, _) => Some(entry),
+            AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(entry)) => Some(entry),
+            _ => None,
+        }
+    }
+}
+
 
 impl From<gloo_file::FileReadError> for AppError {
     fn from(error: gloo_file::FileReadError) -> Self {
@@ -57,8 +93,8 @@ impl From<serde_wasm_bindgen::Error> for AppError {
     fn from(error: serde_wasm_bindgen::Error) -> Self { AppError::SerdeWasmError(error) }
 }
 
-impl From<zip::result::ZipError> for AppError {
-    fn from(error: zip::result::ZipError) -> Self {
+impl From<ZipError> for AppError {
+    fn from(error: ZipError) -> Self {
         AppError::ZipError(error)
     }
 }
@@ -2,14 +2,17 @@ use enclose::enclose;
 use rexie::Rexie;
 use std::rc::Rc;
 use web_sys::MouseEvent;
-use yew::{html, AttrValue, Callback, Component, Context, Html, Properties};
+use yew::{html, Callback, Component, Context, Html, Properties};
 use yew_router::components::Link;
 
+use crate::backup::BackupModal;
+use crate::cover::Cover;
 use crate::icons;
 use crate::models::{Settings, VolumeId, VolumeMetadata};
+use crate::normalize::NormalizeModal;
 use crate::notify::{Notification, Notification::*};
 use crate::upload::UploadModal;
-use crate::utils::db::{delete_volume, get_all_volumes_with_covers, get_settings, put_settings, put_volume};
+use crate::utils::db::{delete_volume, get_all_volumes, get_settings, put_settings, put_volume};
 use crate::Route;
 
 const DELETE_PROMPT: &str = "Are you sure you want to delete this volume?\nThere is no undo!";
@@ -31,30 +34,40 @@ pub enum Message {
     ShowHelp,
     HideModal,
     ShowModal,
+    HideBackup,
+    ShowBackup,
+    HideNormalize,
+    ShowNormalize(VolumeId),
     ToggleSettingsBar,
 }
 
 /// GalleryItems are the volumes which are displayed on the home page.
+/// Their covers are only loaded once they are scrolled into view.
 pub struct GalleryItem {
-    _object_url: gloo_file::ObjectUrl,
-    url: AttrValue,
     volume: VolumeMetadata,
 }
 
 pub struct Home {
+    backup: bool,
     help: bool,
     modal: bool,
+    /// The volume whose text is being normalized.
+    normalize: Option<VolumeId>,
     sidebar: bool,
     settings: Option<Settings>,
     volumes: Vec<GalleryItem>,
 
     commit_settings: Callback<Settings>,
     delete_volume: Callback<VolumeId>,
+    normalize_volume: Callback<VolumeId>,
     update_volume: Callback<(VolumeId, String)>,
     hide_help: Callback<MouseEvent>,
     show_help: Callback<MouseEvent>,
     hide_modal: Callback<MouseEvent>,
     show_modal: Callback<MouseEvent>,
+    hide_backup: Callback<MouseEvent>,
+    show_backup: Callback<MouseEvent>,
+    hide_normalize: Callback<MouseEvent>,
     toggle_settings: Callback<MouseEvent>,
 }
 
@@ -67,23 +80,33 @@ impl Component for Home {
         let show_help = ctx.link().callback(|_| Message::ShowHelp);
         let hide_modal = ctx.link().callback(|_| Message::HideModal);
         let show_modal = ctx.link().callback(|_| Message::ShowModal);
+        let hide_backup = ctx.link().callback(|_| Message::HideBackup);
+        let show_backup = ctx.link().callback(|_| Message::ShowBackup);
+        let hide_normalize = ctx.link().callback(|_| Message::HideNormalize);
         let toggle_settings = ctx.link().callback(|_| Message::ToggleSettingsBar);
         let delete_volume = ctx.link().callback(Message::Delete);
+        let normalize_volume = ctx.link().callback(Message::ShowNormalize);
         let commit_settings = ctx.link().callback(Message::CommitSettings);
         let update_volume = ctx.link().callback(|(id, title)| Message::UpdateVolume(id, title));
         Self {
+            backup: false,
             help: false,
             modal: false,
+            normalize: None,
             sidebar: false,
             settings: None,
             volumes: vec![],
             commit_settings,
             delete_volume,
+            normalize_volume,
             update_volume,
             hide_help,
             show_help,
             hide_modal,
             show_modal,
+            hide_backup,
+            show_backup,
+            hide_normalize,
             toggle_settings,
         }
     }
@@ -142,6 +165,23 @@ impl Component for Home {
                 self.modal = true;
                 true
             }
+            Message::HideBackup => {
+                self.backup = false;
+                ctx.link().send_future(enclose!((db) fetch(db)));
+                false
+            }
+            Message::ShowBackup => {
+                self.backup = true;
+                true
+            }
+            Message::HideNormalize => {
+                self.normalize = None;
+                true
+            }
+            Message::ShowNormalize(volume_id) => {
+                self.normalize = Some(volume_id);
+                true
+            }
             Message::ToggleSettingsBar => {
                 self.sidebar = !self.sidebar;
                 true
@@ -158,14 +198,16 @@ impl Component for Home {
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let Props { db, notify, .. } = ctx.props();
-        let (delete, update) = (&self.delete_volume, &self.update_volume);
+        let (delete, normalize, update) = (&self.delete_volume, &self.normalize_volume, &self.update_volume);
         let gallery: Html =
-            self.volumes.iter().rev().map(|v| v.render(db, notify, delete, update)).collect();
+            self.volumes.iter().rev().map(|v| v.render(db, notify, delete, normalize, update)).collect();
         html! {<>
             <div id="HomeNavBar">
                 <div class="nav-buttons">
                     <div class="settings" onclick={&self.toggle_settings}>{icons::gear()}{"Settings"}</div>
                     <div class="upload" onclick={&self.show_modal}>{icons::upload()}{"Upload"}</div>
+                    <div class="backup" onclick={&self.show_backup}>{"Backup"}</div>
+                    <Link<Route> to={Route::Search}>{"Search"}</Link<Route>>
                 </div>
                 <div class="title">{"Mokuro Library"}</div>
                 <div class="nav-buttons nav-buttons-right">
@@ -176,9 +218,11 @@ impl Component for Home {
             <div id="HomeGrid">
                 if let Some(data) = &self.settings {
                     <settings::SettingsBar
+                        {db} {notify}
                         data={data.clone()}
                         expanded={self.sidebar}
                         commit={&self.commit_settings}
+                        volume_count={self.volumes.len()}
                     />
                 }
                 <div id="GalleryContainer">
@@ -190,6 +234,12 @@ impl Component for Home {
             if self.modal {
                 <UploadModal {db} {notify} close_modal={&self.hide_modal}/>
             }
+            if self.backup {
+                <BackupModal {db} {notify} close_modal={&self.hide_backup}/>
+            }
+            if let Some(volume_id) = self.normalize {
+                <NormalizeModal {db} {notify} {volume_id} close_modal={&self.hide_normalize}/>
+            }
         </>}
     }
 }
@@ -200,20 +250,23 @@ impl GalleryItem {
         db: &Rc<Rexie>,
         notify: &Callback<Notification>,
         delete_cb: &Callback<VolumeId>,
+        normalize_cb: &Callback<VolumeId>,
         update_cb: &Callback<(VolumeId, String)>,
     ) -> Html {
         let volume_id = self.volume.id;
         let onclick = delete_cb.reform(move |_| volume_id);
+        let normalize = normalize_cb.reform(move |_| volume_id);
         let commit = update_cb.reform(move |new_title: String| (volume_id, new_title));
         let title = &self.volume.title;
         html! {
-            <div class="volume-item">
+            <div class="volume-item" key={volume_id}>
                 <Link<Route> to={Route::Reader {volume_id}}>
-                    <img src={&self.url} alt={title}/>
+                    <Cover {db} {notify} {volume_id} page={self.volume.cover()} alt={title} thumbnail=true/>
                 </Link<Route>>
                 <title::EditableTitle {title} {commit} {notify}/>
                 <download::DownloadButton {db} {notify} {volume_id}/>
                 <button class="delete" {onclick}>{"Delete"}</button>
+                <button class="normalize" onclick={normalize}>{"Normalize Text"}</button>
             </div>
         }
     }
@@ -227,20 +280,14 @@ async fn fetch(db: Rc<Rexie>) -> Message {
         )
     };
 
-    let pairs = match get_all_volumes_with_covers(&db).await {
-        Ok(pairs) => pairs,
+    let volumes = match get_all_volumes(db).await {
+        Ok(volumes) => volumes,
         Err(err) => return Message::Notify(
             Warning("failed to retrieve all volumes from IndexedDB", err.to_string())
         )
     };
 
-    let mut items = Vec::with_capacity(pairs.len());
-    for (volume, page) in pairs.into_iter() {
-        let _object_url = gloo_file::ObjectUrl::from(page);
-        let url = AttrValue::from(_object_url.to_string());
-        items.push(GalleryItem { _object_url, url, volume })
-    }
-
+    let items = volumes.into_iter().map(|volume| GalleryItem { volume }).collect();
     Message::Set(settings, items)
 }
 
@@ -278,6 +325,7 @@ mod download {
     use crate::utils::zip::create_ziparchive;
     use enclose::enclose;
     use rexie::Rexie;
+    use std::cell::Cell;
     use std::cmp::PartialEq;
     use std::rc::Rc;
     use web_sys::MouseEvent;
@@ -292,6 +340,9 @@ mod download {
 
     pub enum Message {
         Request,
+        Progress(usize, usize),
+        Cancel,
+        Cancelled,
         Set(gloo_file::File),
         Notify(Notification),
     }
@@ -299,7 +350,8 @@ mod download {
 
     enum State {
         Default,
-        Processing,
+        /// (pages written, total pages) and the flag used to cancel the export.
+        Processing(Option<(usize, usize)>, Rc<Cell<bool>>),
         Ready(File),
     }
 
@@ -312,6 +364,7 @@ mod download {
     pub struct DownloadButton {
         state: State,
         onclick: Callback<MouseEvent>,
+        oncancel: Callback<MouseEvent>,
     }
 
     impl Component for DownloadButton {
@@ -320,9 +373,11 @@ mod download {
 
         fn create(ctx: &Context<Self>) -> Self {
             let onclick = ctx.link().callback(|_| Message::Request);
+            let oncancel = ctx.link().callback(|_| Message::Cancel);
             Self {
                 state: State::Default,
                 onclick,
+                oncancel,
             }
         }
 
@@ -330,12 +385,30 @@ mod download {
             let Props { db, notify, volume_id } = ctx.props();
             match msg {
                 Message::Request => {
-                    self.state = State::Processing;
+                    let cancelled = Rc::new(Cell::new(false));
+                    let progress = ctx.link().callback(|(n, m)| Message::Progress(n, m));
+                    self.state = State::Processing(None, cancelled.clone());
                     ctx.link().send_future(enclose!(
-                        (db, volume_id) fetch(db, volume_id)
+                        (db, volume_id) fetch(db, volume_id, progress, cancelled)
                     ));
                     true
                 }
+                Message::Progress(n, m) => {
+                    if let State::Processing(progress, _) = &mut self.state {
+                        *progress = Some((n, m));
+                        return true;
+                    }
+                    false
+                }
+                Message::Cancel => {
+                    if let State::Processing(_, cancelled) = &self.state {
+                        cancelled.set(true);
+                        self.state = State::Default;
+                        return true;
+                    }
+                    false
+                }
+                Message::Cancelled => false,
                 Message::Set(file) => {
                     let _url_object = gloo_file::ObjectUrl::from(file.clone());
                     let url = AttrValue::from(_url_object.to_string());
@@ -343,8 +416,11 @@ mod download {
                     true
                 }
                 Message::Notify(notification) => {
+                    if let State::Processing(..) = self.state {
+                        self.state = State::Default;
+                    }
                     notify.emit(notification);
-                    false
+                    true
                 }
             }
         }
@@ -355,7 +431,16 @@ mod download {
                 State::Default => {
                     html! { <button {class} onclick={&self.onclick}>{"Prepare Download"}</button> }
                 }
-                State::Processing => html! { <button {class}>{"Preparing..."}</button> },
+                State::Processing(progress, _) => {
+                    let text = match progress {
+                        Some((n, m)) => format!("page {n} / {m}"),
+                        None => "Preparing...".to_string(),
+                    };
+                    html! {<>
+                        <button class="download progress">{text}</button>
+                        <button class="cancel" onclick={&self.oncancel}>{"Cancel"}</button>
+                    </>}
+                }
                 State::Ready(file) => {
                     html! {
                         <a href={&file.url} download={file.file.name()}>
@@ -367,9 +452,14 @@ mod download {
         }
     }
 
-    async fn fetch(db: Rc<Rexie>, volume_id: VolumeId) -> Message {
-        match create_ziparchive(db.clone(), volume_id).await {
-            Ok(file) => Message::Set(file),
+    async fn fetch(
+        db: Rc<Rexie>, volume_id: VolumeId,
+        progress: Callback<(usize, usize)>, cancelled: Rc<Cell<bool>>,
+    ) -> Message {
+        match create_ziparchive(db.clone(), volume_id, progress, cancelled.clone()).await {
+            Ok(Some(file)) => Message::Set(file),
+            Ok(None) => Message::Cancelled,
+            Err(_) if cancelled.get() => Message::Cancelled,
             Err(err) => Message::Notify(Warning("failed to create zip archive for download", err.to_string()))
         }
     }
@@ -402,7 +492,12 @@ mod help {
                     library. The titles of your volumes can be edited by double-clicking them. \
                     Additionally, your volumes can be exported and downloaded as .mbz.zip files \
                     from the Home/Library page by clicking the \"Prepare Download\" button and \
-                    then the \"Download\" button."
+                    then the \"Download\" button. The \"Backup\" button backs up your entire \
+                    library (every volume and your settings) into a single archive, which \
+                    can be restored from the same place. The \"Search\" button searches the \
+                    text of your entire library, a series, or a single volume. The \"Normalize \
+                    Text\" button of a volume makes its punctuation, whitespace and half-width \
+                    or full-width characters consistent, after showing you the changes."
                     }</p>
                     <p>{
                     "When uploading volumes, you will be prompted to \"Persist Your Storage\". \
@@ -437,17 +532,24 @@ mod help {
 }
 
 mod settings {
+    use rexie::Rexie;
+    use std::rc::Rc;
     use web_sys::Event;
     use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
 
     use crate::models::{MagnifierSettings, Settings};
+    use crate::notify::Notification;
     use crate::utils::web::{get_input_u16, get_input_u8};
 
     #[derive(Properties, PartialEq)]
     pub struct Props {
+        pub db: Rc<Rexie>,
+        pub notify: Callback<Notification>,
         pub data: Settings,
         pub expanded: bool,
         pub commit: Callback<Settings>,
+        /// The number of volumes in the library, so that storage use is recalculated when it changes.
+        pub volume_count: usize,
     }
 
     pub struct SettingsBar {
@@ -497,7 +599,8 @@ mod settings {
                             radius: magnifier_radius,
                             height: magnifier_height,
                             width: magnifier_width,
-                        }
+                        },
+                        ..data.clone()
                     };
                     if new_data != *data {
                         commit.emit(new_data);
@@ -508,7 +611,7 @@ mod settings {
         }
 
         fn view(&self, ctx: &Context<Self>) -> Html {
-            let Props { data, expanded, .. } = ctx.props();
+            let Props { db, notify, data, expanded, volume_count, .. } = ctx.props();
             let hidden = !expanded;
             html! {
                 <div id="SideBar" tabindex={"2"} class={"expanded"} {hidden}>
@@ -557,12 +660,180 @@ mod settings {
                             onchange={&self.onchange}
                         />
                     </div>
+
+                    if *expanded {
+                        <super::storage::StoragePanel {db} {notify} volume_count={volume_count}/>
+                    }
                 </div>
             }
         }
     }
 }
 
+mod storage {
+    use enclose::enclose;
+    use rexie::Rexie;
+    use std::rc::Rc;
+    use web_sys::{Event, HtmlSelectElement};
+    use yew::{html, AttrValue, Callback, Component, Context, Html, Properties, TargetCast};
+
+    use crate::models::VolumeId;
+    use crate::notify::{Notification, Notification::Warning};
+    use crate::utils::db::get_volume_sizes;
+    use crate::utils::web::{get_storage_estimate, is_web_storage_persisted};
+
+    #[derive(Properties, PartialEq)]
+    pub struct Props {
+        pub db: Rc<Rexie>,
+        pub notify: Callback<Notification>,
+        pub volume_count: usize,
+    }
+
+    pub enum Message {
+        Set(Box<Usage>),
+        Sort(bool),
+        Notify(Notification),
+    }
+
+    /// The storage used by the library, as reported by the browser and measured per volume.
+    pub struct Usage {
+        /// (usage, quota) in bytes, if the browser provides an estimate.
+        estimate: Option<(f64, f64)>,
+        persisted: Option<bool>,
+        volumes: Vec<VolumeUsage>,
+    }
+
+    struct VolumeUsage {
+        id: VolumeId,
+        title: AttrValue,
+        images: u64,
+        ocr: u64,
+    }
+
+    /// StoragePanel shows how much of the browser's storage the library is using,
+    /// so that the largest volumes can be found before the browser starts evicting data.
+    pub struct StoragePanel {
+        usage: Option<Usage>,
+        by_size: bool,
+        onsort: Callback<Event>,
+    }
+
+    impl Component for StoragePanel {
+        type Message = Message;
+        type Properties = Props;
+
+        fn create(ctx: &Context<Self>) -> Self {
+            let db = &ctx.props().db;
+            ctx.link().send_future(enclose!((db) fetch(db)));
+            let onsort = ctx.link().callback(|e: Event| {
+                let select: HtmlSelectElement = e.target_unchecked_into();
+                Message::Sort(select.value() == "size")
+            });
+            Self { usage: None, by_size: true, onsort }
+        }
+
+        fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+            match msg {
+                Message::Set(usage) => {
+                    self.usage = Some(*usage);
+                    true
+                }
+                Message::Sort(by_size) => {
+                    self.by_size = by_size;
+                    true
+                }
+                Message::Notify(notification) => {
+                    ctx.props().notify.emit(notification);
+                    false
+                }
+            }
+        }
+
+        fn changed(&mut self, ctx: &Context<Self>, old_props: &Self::Properties) -> bool {
+            let Props { db, volume_count, .. } = ctx.props();
+            if *volume_count != old_props.volume_count {
+                ctx.link().send_future(enclose!((db) fetch(db)));
+            }
+            false
+        }
+
+        fn view(&self, _ctx: &Context<Self>) -> Html {
+            let Some(usage) = &self.usage else {
+                return html! {<>
+                    <h3 class="sidebar-header">{"Storage"}</h3>
+                    <p class="sidebar-storage">{"Calculating..."}</p>
+                </>};
+            };
+            let estimate = match usage.estimate {
+                Some((used, quota)) => format!("{} of {} used", format_size(used), format_size(quota)),
+                None => "Usage estimate unavailable".to_string(),
+            };
+            let persisted = match usage.persisted {
+                Some(true) => "Storage is persisted",
+                Some(false) => "Storage is NOT persisted",
+                None => "Persistence unknown",
+            };
+            let mut volumes: Vec<&VolumeUsage> = usage.volumes.iter().collect();
+            if self.by_size {
+                volumes.sort_by_key(|v| std::cmp::Reverse(v.images + v.ocr));
+            } else {
+                volumes.sort_by_key(|v| std::cmp::Reverse(v.id));
+            }
+            let rows: Html = volumes.into_iter().map(|v| {
+                let title = format!(
+                    "{}\nimages: {}\nocr: {}", v.title, format_size(v.images as f64), format_size(v.ocr as f64)
+                );
+                html! {
+                    <div class="sidebar-storage-volume" {title}>
+                        <span>{&v.title}</span>
+                        <span>{format_size((v.images + v.ocr) as f64)}</span>
+                    </div>
+                }
+            }).collect();
+            html! {<>
+                <h3 class="sidebar-header">{"Storage"}</h3>
+                <p class="sidebar-storage">{estimate}</p>
+                <p class="sidebar-storage">{persisted}</p>
+                <div class="sidebar-input-container">
+                    <label for="storage-sort">{"Sort"}</label>
+                    <select id="storage-sort" onchange={&self.onsort}>
+                        <option value="size" selected={self.by_size}>{"Largest"}</option>
+                        <option value="recent" selected={!self.by_size}>{"Newest"}</option>
+                    </select>
+                </div>
+                {rows}
+            </>}
+        }
+    }
+
+    async fn fetch(db: Rc<Rexie>) -> Message {
+        let volumes = match get_volume_sizes(&db).await {
+            Ok(volumes) => volumes,
+            Err(err) => return Message::Notify(
+                Warning("failed to calculate the storage used by each volume", err.to_string())
+            ),
+        };
+        let volumes = volumes.into_iter().map(|(volume, images, ocr)| {
+            VolumeUsage { id: volume.id, title: volume.title, images, ocr }
+        }).collect();
+        let estimate = get_storage_estimate().await.ok();
+        let persisted = is_web_storage_persisted().await.ok();
+        Message::Set(Box::new(Usage { estimate, persisted, volumes }))
+    }
+
+    /// Formats a number of bytes in the largest unit which keeps it above one.
+    fn format_size(bytes: f64) -> String {
+        let units = ["B", "KB", "MB", "GB", "TB"];
+        let mut size = bytes;
+        let mut unit = 0;
+        while size >= 1024.0 && unit < units.len() - 1 {
+            size /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 { format!("{size} {}", units[unit]) } else { format!("{size:.1} {}", units[unit]) }
+    }
+}
+
 mod title {
     use crate::notify::{Notification, Notification::Warning};
     use crate::utils::web::set_caret;
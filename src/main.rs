@@ -10,14 +10,19 @@ use crate::home::Home;
 use crate::models::VolumeId;
 use crate::notify::{Notification, NotificationProvider};
 use crate::reader::Reader;
+use crate::search::Search;
 use crate::utils::db::create_database;
 
 mod utils;
 mod models;
 mod errors;
 mod upload;
+mod backup;
+mod cover;
 mod home;
+mod normalize;
 mod reader;
+mod search;
 mod icons;
 mod notify;
 
@@ -89,6 +94,8 @@ async fn initialize() -> Message {
 enum Route {
     #[at("/")]
     Home,
+    #[at("/search")]
+    Search,
     #[at("/volume/:volume_id/reader")]
     Reader { volume_id: VolumeId },
     #[not_found]
@@ -99,6 +106,7 @@ enum Route {
 fn switch(db: &Rc<Rexie>, route: Route, notify: Callback<Notification>) -> Html {
     match route {
         Route::Home => html! { <Home {db} {notify}/> },
+        Route::Search => html! { <Search {db} {notify}/> },
         Route::Reader { volume_id } => html! { <Reader {db} {notify} {volume_id} /> },
         Route::NotFound => html! { <h1>{ "404" }</h1> },
     }
@@ -1,15 +1,26 @@
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
 use yew::AttrValue;
 
+pub use lint::{CharClass, LintContext, LintRule};
 pub use magnifier::MagnifierSettings;
-pub use reader_state::ReaderState;
+pub use reader_state::{PageMode, PairingBreak, ReaderState, ReadingDirection};
 
 pub type VolumeId = usize;
 
 
-#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub struct Settings {
     pub magnifier: MagnifierSettings,
+    /// The rules of the OCR linter (see utils::lint).
+    #[serde(default = "LintRule::defaults")]
+    pub lint_rules: Vec<LintRule>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { magnifier: MagnifierSettings::default(), lint_rules: LintRule::defaults() }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
@@ -44,12 +55,167 @@ pub struct VolumeMetadata {
     pub magnifier: MagnifierSettings,
     #[serde(default)]
     pub reader_state: ReaderState,
+    #[serde(default)]
+    pub bookmarks: Vec<PageMarker>,
+    #[serde(default)]
+    pub chapters: Vec<PageMarker>,
+    /// The indices of the pages which are wider than they are tall,
+    /// i.e. double-page spreads which were merged into a single image.
+    /// This is None if the page dimensions have not been checked yet.
+    #[serde(default)]
+    pub wide_pages: Option<Vec<usize>>,
+    /// Textboxes which were changed in both copies of a merged volume,
+    /// which are waiting for the user to choose between the two versions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<BlockConflict>,
+    /// Set while the pages of the volume are being written, so that a volume whose
+    /// import was interrupted (e.g. the tab was closed) is hidden, and deleted when
+    /// the database is opened once the import is a day old.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub importing: bool,
+    /// When the import started (in milliseconds since UNIX_EPOCH), so that the imports
+    /// still in progress in another tab are not deleted along with the interrupted ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub import_started: Option<u64>,
+
+    /// Fields which are not modelled by this app, e.g. fields written by
+    /// newer versions of mokuro. These are kept so that they survive the
+    /// round trip of importing and exporting a volume.
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+/// A marked page of a volume, i.e. a bookmark or the start of a chapter.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct PageMarker {
+    pub page: usize,
+    #[serde(default)]
+    pub label: Option<AttrValue>,
+}
+
+impl PageMarker {
+    /// The text displayed for this marker, which falls back to the page number.
+    pub fn display(&self) -> String {
+        match &self.label {
+            Some(label) => format!("{} (p. {})", label, self.page + 1),
+            None => format!("Page {}", self.page + 1),
+        }
+    }
+}
+
+/// A textbox which was changed differently in two copies of a volume.
+/// A `None` block means the textbox was deleted from that copy.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlockConflict {
+    pub page: usize,
+    /// Where the block is inserted into the page if it does not exist.
+    pub index: usize,
+    pub ours: Option<OcrBlock>,
+    pub theirs: Option<OcrBlock>,
+}
+
+impl BlockConflict {
+    pub fn uuid(&self) -> Option<&AttrValue> {
+        self.ours.as_ref().or(self.theirs.as_ref()).map(|block| &block.uuid)
+    }
 }
 
 fn is_zero(value: &VolumeId) -> bool { *value == 0 }
 
 fn default_line_height() -> f64 { 1.0f64 }
 
+mod lint {
+    use serde::{Deserialize, Serialize};
+    use yew::AttrValue;
+
+    /// A class of characters, which the context of a lint rule is described by.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum CharClass {
+        Hiragana,
+        Katakana,
+        /// Hiragana or Katakana.
+        Kana,
+        Kanji,
+        /// Kana, Kanji, or Japanese (full-width) punctuation.
+        Japanese,
+        Ascii,
+        /// The kana of the い row (き, し, ち, ...), which small ゃ, ゅ and ょ follow.
+        IRow,
+    }
+
+    /// Where the text of a lint rule must be found for it to be suspicious, e.g.
+    /// `{"neighbour": "katakana"}` or `"always"`. Neighbours are the characters
+    /// immediately before and after the text, within the same line.
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+    #[serde(rename_all = "snake_case")]
+    pub enum LintContext {
+        #[default]
+        Always,
+        /// Either neighbour is of the class.
+        Neighbour(CharClass),
+        /// Both neighbours are of the class.
+        Between(CharClass),
+        /// The preceding character is of the class.
+        After(CharClass),
+        /// The preceding character is not of the class, or the text starts the line.
+        NotAfter(CharClass),
+    }
+
+    /// A lint rule flags each occurrence of `find` within its context,
+    /// which can be fixed by replacing it with `replace`.
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+    pub struct LintRule {
+        pub name: AttrValue,
+        pub find: AttrValue,
+        pub replace: AttrValue,
+        #[serde(default)]
+        pub context: LintContext,
+        #[serde(default = "enabled")]
+        pub enabled: bool,
+    }
+
+    fn enabled() -> bool { true }
+
+    impl LintRule {
+        pub fn new(name: &'static str, find: &'static str, replace: &'static str, context: LintContext) -> Self {
+            Self { name: name.into(), find: find.into(), replace: replace.into(), context, enabled: true }
+        }
+
+        /// The rules for the characters which OCR most often confuses.
+        pub fn defaults() -> Vec<Self> {
+            use CharClass::*;
+            use LintContext::*;
+            vec![
+                Self::new("力 → カ (katakana)", "力", "カ", Neighbour(Katakana)),
+                Self::new("カ → 力 (kanji)", "カ", "力", After(Kanji)),
+                Self::new("口 → ロ (katakana)", "口", "ロ", Neighbour(Katakana)),
+                Self::new("ロ → 口 (kanji)", "ロ", "口", After(Kanji)),
+                Self::new("一 → ー (katakana)", "一", "ー", After(Katakana)),
+                Self::new("－ → ー (katakana)", "－", "ー", After(Katakana)),
+                Self::new("ー → 一 (kanji)", "ー", "一", After(Kanji)),
+                Self::new("二 → ニ (katakana)", "二", "ニ", Neighbour(Katakana)),
+                Self::new("ニ → 二 (kanji)", "ニ", "二", After(Kanji)),
+                Self::new("工 → エ (katakana)", "工", "エ", Neighbour(Katakana)),
+                Self::new("エ → 工 (kanji)", "エ", "工", After(Kanji)),
+                Self::new("ゃ → や", "ゃ", "や", NotAfter(IRow)),
+                Self::new("ゅ → ゆ", "ゅ", "ゆ", NotAfter(IRow)),
+                Self::new("ょ → よ", "ょ", "よ", NotAfter(IRow)),
+                Self::new("ャ → ヤ", "ャ", "ヤ", NotAfter(IRow)),
+                Self::new("ュ → ユ", "ュ", "ユ", NotAfter(IRow)),
+                Self::new("ョ → ヨ", "ョ", "ヨ", NotAfter(IRow)),
+                Self::new("! → ！", "!", "！", Neighbour(Japanese)),
+                Self::new("? → ？", "?", "？", Neighbour(Japanese)),
+                Self::new(", → 、", ",", "、", Neighbour(Japanese)),
+                Self::new("~ → ～", "~", "～", Neighbour(Japanese)),
+                Self::new(": → ：", ":", "：", Neighbour(Japanese)),
+                Self::new("( → （", "(", "（", Neighbour(Japanese)),
+                Self::new(") → ）", ")", "）", Neighbour(Japanese)),
+            ]
+        }
+    }
+}
+
 mod magnifier {
     use serde::{Deserialize, Serialize};
 
@@ -77,25 +243,180 @@ mod magnifier {
 mod reader_state {
     use serde::{Deserialize, Serialize};
 
-    #[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
-    #[serde(default)]
+    #[derive(Serialize, Deserialize, Clone, PartialEq)]
+    #[serde(from = "StoredReaderState")]
     pub struct ReaderState {
-        pub single_page: bool,
+        pub page_mode: PageMode,
         pub current_page: usize,
         pub first_page_is_cover: bool,
+        pub direction: ReadingDirection,
+        pub pairing_breaks: Vec<PairingBreak>,
     }
 
     impl Default for ReaderState {
         fn default() -> Self {
             Self {
-                single_page: false,
+                page_mode: PageMode::default(),
                 current_page: 0,
                 first_page_is_cover: true,
+                direction: ReadingDirection::default(),
+                pairing_breaks: Vec::default(),
+            }
+        }
+    }
+
+    /// The reader state as it may have been stored by older versions,
+    /// which had a `single_page` flag in place of the page mode.
+    #[derive(Deserialize)]
+    #[serde(default)]
+    struct StoredReaderState {
+        page_mode: Option<PageMode>,
+        single_page: Option<bool>,
+        current_page: usize,
+        first_page_is_cover: bool,
+        direction: ReadingDirection,
+        pairing_breaks: Vec<PairingBreak>,
+    }
+
+    impl Default for StoredReaderState {
+        fn default() -> Self {
+            let ReaderState { current_page, first_page_is_cover, direction, pairing_breaks, .. } =
+                ReaderState::default();
+            Self { page_mode: None, single_page: None, current_page, first_page_is_cover, direction, pairing_breaks }
+        }
+    }
+
+    impl From<StoredReaderState> for ReaderState {
+        fn from(stored: StoredReaderState) -> Self {
+            let legacy = stored.single_page.map(|single| if single { PageMode::Single } else { PageMode::Double });
+            Self {
+                page_mode: stored.page_mode.or(legacy).unwrap_or_default(),
+                current_page: stored.current_page,
+                first_page_is_cover: stored.first_page_is_cover,
+                direction: stored.direction,
+                pairing_breaks: stored.pairing_breaks,
             }
         }
     }
+
+    /// How the pages of a volume are paired together.
+    ///   - Double: pages are displayed in pairs.
+    ///   - Single: pages are displayed one at a time.
+    ///   - Auto: pages are displayed in pairs, except for wide pages
+    ///     (spreads) which are displayed alone.
+    #[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+    pub enum PageMode {
+        #[default]
+        Double,
+        Single,
+        Auto,
+    }
+
+    /// A manual adjustment to the page pairing, at the page with the given index.
+    /// This is used to correct the pairing of scans which contain inserts
+    /// or are missing pages.
+    #[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+    pub enum PairingBreak {
+        /// A virtual blank page is inserted before the page.
+        Blank(usize),
+        /// The page is displayed alone.
+        Single(usize),
+    }
+
+    impl PairingBreak {
+        pub fn page(&self) -> usize {
+            match self {
+                Self::Blank(index) | Self::Single(index) => *index,
+            }
+        }
+    }
+
+    impl ReaderState {
+        /// Cycles the pairing break of a page: none -> blank -> single -> none.
+        pub fn toggle_pairing_break(&mut self, index: usize) {
+            let Some(position) = self.pairing_breaks.iter().position(|b| b.page() == index) else {
+                self.pairing_breaks.push(PairingBreak::Blank(index));
+                return;
+            };
+            match self.pairing_breaks[position] {
+                PairingBreak::Blank(_) => self.pairing_breaks[position] = PairingBreak::Single(index),
+                PairingBreak::Single(_) => { self.pairing_breaks.remove(position); }
+            }
+        }
+    }
+
+    /// The order in which the pages of a spread are laid out.
+    /// Manga is read right-to-left, i.e. the first page of a spread is on the right.
+    #[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+    pub enum ReadingDirection {
+        #[default]
+        RightToLeft,
+        LeftToRight,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn page_mode(json: &str) -> PageMode {
+            serde_json::from_str::<ReaderState>(json).unwrap().page_mode
+        }
+
+        #[test]
+        fn maps_single_page_onto_page_mode() {
+            assert!(page_mode(r#"{"single_page": true}"#) == PageMode::Single);
+            assert!(page_mode(r#"{"single_page": false}"#) == PageMode::Double);
+            assert!(page_mode(r#"{"single_page": true, "page_mode": "Auto"}"#) == PageMode::Auto);
+            assert!(page_mode("{}") == PageMode::Double);
+            assert!(serde_json::from_str::<ReaderState>("{}").unwrap() == ReaderState::default());
+        }
+    }
+}
+
+
+impl VolumeMetadata {
+    /// Creates the metadata for a volume which was not created by the mokuro fork,
+    /// generating the uuid and timestamps if they are missing from the source.
+    pub fn new(
+        title: AttrValue,
+        volume: AttrValue,
+        volume_uuid: Option<AttrValue>,
+        pages: Box<[(AttrValue, AttrValue)]>,
+    ) -> Self {
+        let now: AttrValue = crate::utils::iso_timestamp().into();
+        let volume_uuid = volume_uuid.unwrap_or_else(|| {
+            let ts = uuid::Timestamp::now(uuid::NoContext);
+            uuid::Uuid::new_v7(ts).hyphenated().to_string().into()
+        });
+        Self {
+            created_at: now.clone(),
+            modified_at: now,
+            title,
+            volume,
+            volume_uuid,
+            pages,
+            line_height: default_line_height(),
+            ..Self::default()
+        }
+    }
 }
 
+impl VolumeMetadata {
+    /// Keeps the reading progress, markers and settings of an existing copy of this
+    /// volume, dropping any which refer to pages that no longer exist.
+    /// Everything else, including the title, is taken from this copy.
+    pub fn keep_progress(&mut self, existing: &VolumeMetadata) {
+        let len = self.pages.len();
+        self.hide_sidebar = existing.hide_sidebar;
+        self.line_height = existing.line_height;
+        self.magnifier = existing.magnifier;
+        self.reader_state = existing.reader_state.clone();
+        self.reader_state.current_page = self.reader_state.current_page.min(len.saturating_sub(1));
+        self.reader_state.pairing_breaks.retain(|b| b.page() < len);
+        self.bookmarks = existing.bookmarks.iter().filter(|m| m.page < len).cloned().collect();
+        self.chapters = existing.chapters.iter().filter(|m| m.page < len).cloned().collect();
+    }
+}
 
 impl<'a> VolumeMetadata {
     /// Convenience method for getting the name of cover art,
@@ -107,56 +428,165 @@ impl<'a> VolumeMetadata {
         &self.pages[0].0
     }
 
+    /// Groups the pages of the volume into the spreads which are displayed
+    /// together, as (first, second) page indices in reading order.
+    /// A page which is paired with a virtual blank page is displayed alone.
+    pub fn spreads(&self) -> Vec<(usize, Option<usize>)> {
+        let ReaderState { page_mode, first_page_is_cover, .. } = self.reader_state;
+        let breaks = &self.reader_state.pairing_breaks;
+        let wide_pages = self.wide_pages.as_deref().unwrap_or_default();
+
+        let mut spreads = Vec::with_capacity(self.pages.len());
+        // The first half of the current spread, where Some(None) is a blank page.
+        let mut pending: Option<Option<usize>> = None;
+        for index in 0..self.pages.len() {
+            if breaks.contains(&PairingBreak::Blank(index)) {
+                match pending.take() {
+                    Some(Some(first)) => spreads.push((first, None)),
+                    Some(None) => {}
+                    None => pending = Some(None),
+                }
+            }
+
+            let alone = (index == 0 && first_page_is_cover)
+                || breaks.contains(&PairingBreak::Single(index))
+                || match page_mode {
+                PageMode::Double => false,
+                PageMode::Single => true,
+                PageMode::Auto => wide_pages.contains(&index),
+            };
+            if alone {
+                // Any unpaired page before a lone page is also displayed alone.
+                spreads.extend(pending.take().flatten().map(|first| (first, None)));
+                spreads.push((index, None));
+                continue;
+            }
+            match pending.take() {
+                Some(Some(first)) => spreads.push((first, Some(index))),
+                Some(None) => spreads.push((index, None)),
+                None => pending = Some(Some(index)),
+            }
+        }
+        spreads.extend(pending.flatten().map(|first| (first, None)));
+        spreads
+    }
+
+    /// Finds the spread which contains the page at `index`.
+    fn spread_position(spreads: &[(usize, Option<usize>)], index: usize) -> usize {
+        spreads.iter().rposition(|(first, _)| *first <= index).unwrap_or(0)
+    }
+
     pub fn page_forward(&mut self) {
-        let ReaderState {
-            single_page, current_page, first_page_is_cover
-        } = self.reader_state;
-        let len = self.pages.len();
-        let increment = match (current_page, single_page, first_page_is_cover) {
-            (p, _, _) if p >= (len - 1) => 0,
-            (p, _, _) if p == (len - 2) => 1,
-            (p, _, true) if p % 2 == 0 => 1,
-            (0.., true, _) => 1,
-            (0.., false, _) => 2,
-        };
-        self.reader_state.current_page += increment;
+        let spreads = self.spreads();
+        let position = Self::spread_position(&spreads, self.reader_state.current_page);
+        if let Some((first, _)) = spreads.get(position + 1) {
+            self.reader_state.current_page = *first;
+        }
     }
 
     pub fn page_backward(&mut self) {
-        let ReaderState {
-            current_page, single_page, first_page_is_cover
-        } = self.reader_state;
-        let decrement = match (current_page, single_page, first_page_is_cover) {
-            (0, _, _) => 0,
-            (1, _, _) => 1,
-            (2.., true, _) => 1,
-            (p, _, true) if p % 2 == 0 => 1,
-            (2.., false, _) => 2,
-        };
-        self.reader_state.current_page -= decrement;
+        let spreads = self.spreads();
+        let position = Self::spread_position(&spreads, self.reader_state.current_page);
+        if let Some((first, _)) = position.checked_sub(1).and_then(|p| spreads.get(p)) {
+            self.reader_state.current_page = *first;
+        }
     }
 
+    /// Moves the reader to the spread which contains the page at `index`.
+    pub fn go_to_page(&mut self, index: usize) {
+        let spreads = self.spreads();
+        let position = Self::spread_position(&spreads, index);
+        if let Some((first, _)) = spreads.get(position) {
+            self.reader_state.current_page = *first;
+        }
+    }
+
+    /// Removes the bookmark of the page at `index` if it exists, otherwise adds one.
+    pub fn toggle_bookmark(&mut self, index: usize) {
+        if !remove_marker(&mut self.bookmarks, index) {
+            insert_marker(&mut self.bookmarks, PageMarker { page: index, label: None });
+        }
+    }
+
+    /// Whether a chapter begins at the page at `index`.
+    pub fn has_chapter(&self, index: usize) -> bool {
+        self.chapters.iter().any(|marker| marker.page == index)
+    }
+
+    /// Marks the page at `index` as the beginning of a chapter.
+    pub fn add_chapter(&mut self, index: usize, label: Option<AttrValue>) {
+        remove_marker(&mut self.chapters, index);
+        insert_marker(&mut self.chapters, PageMarker { page: index, label });
+    }
+
+    pub fn remove_chapter(&mut self, index: usize) {
+        remove_marker(&mut self.chapters, index);
+    }
+
+    /// Returns the index of the page with the given name.
+    pub fn page_index(&self, name: &str) -> Option<usize> {
+        self.pages.iter().position(|(page_name, _)| page_name == name)
+    }
+
+    /// The pages of the spreads before and after the current spread,
+    /// i.e. the pages which are displayed after the next page turn.
+    pub fn adjacent_pages(&self) -> Vec<AttrValue> {
+        let spreads = self.spreads();
+        let position = Self::spread_position(&spreads, self.reader_state.current_page);
+        [position + 1, position.wrapping_sub(1)].iter()
+            .filter_map(|p| spreads.get(*p))
+            .flat_map(|(first, second)| std::iter::once(*first).chain(*second))
+            .filter_map(|index| self.pages.get(index).map(|(page_name, _)| page_name.clone()))
+            .collect()
+    }
+
+    /// Selects the pages which are currently displayed, as (left, right).
+    /// If only a single page is displayed, it is always the right page.
     pub fn select_pages(&self) -> (Option<AttrValue>, Option<AttrValue>) {
         let get_page = |i: usize| -> Option<AttrValue> {
             self.pages.get(i).map(|p| p.0.clone())
         };
-        let ReaderState { single_page, current_page, first_page_is_cover } = self.reader_state;
-        if single_page || (current_page == 0 && first_page_is_cover) {
-            return (get_page(current_page), None);
+        let ReaderState { current_page, direction, .. } = self.reader_state;
+        let spreads = self.spreads();
+        let Some((first, second)) =
+            spreads.get(Self::spread_position(&spreads, current_page)) else {
+            return (None, None);
+        };
+        let (first, second) = (get_page(*first), second.and_then(get_page));
+        match (direction, second) {
+            (_, None) => (None, first),
+            (ReadingDirection::RightToLeft, second) => (second, first),
+            (ReadingDirection::LeftToRight, second) => (first, second),
         }
-        (get_page(current_page), get_page(current_page + 1))
     }
 }
 
+/// Inserts the marker, keeping the markers sorted by page.
+fn insert_marker(markers: &mut Vec<PageMarker>, marker: PageMarker) {
+    let position = markers.partition_point(|m| m.page < marker.page);
+    markers.insert(position, marker);
+}
+
+/// Removes the marker of the page at `index`, returning whether one existed.
+fn remove_marker(markers: &mut Vec<PageMarker>, index: usize) -> bool {
+    let length = markers.len();
+    markers.retain(|marker| marker.page != index);
+    markers.len() != length
+}
+
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct PageOcr {
     pub img_width: u32,
     pub img_height: u32,
     pub blocks: Vec<OcrBlock>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
 pub struct OcrBlock {
+    /// Generated if missing, as upstream mokuro does not assign ids to blocks.
+    #[serde(default = "block_uuid")]
     pub uuid: AttrValue,
     #[serde(rename = "box")]
     // (left, top, right, bottom)
@@ -164,7 +594,23 @@ pub struct OcrBlock {
     pub vertical: bool,
     pub font_size: u32,
     pub lines: Vec<AttrValue>,
-    // lines_coords: Vec<Vec<(f32, f32)>>,
+    /// The polygon (a list of (x, y) points) which bounds each line, in image pixels.
+    #[serde(default)]
+    pub lines_coords: Vec<Vec<(f64, f64)>>,
+    #[serde(flatten)]
+    pub extra: Map<String, Value>,
+}
+
+fn block_uuid() -> AttrValue {
+    let ts = uuid::Timestamp::now(uuid::NoContext);
+    uuid::Uuid::new_v7(ts).simple().to_string().into()
+}
+
+impl PageOcr {
+    /// Whether the page is wider than it is tall, i.e. a double-page spread.
+    pub fn is_wide(&self) -> bool {
+        self.img_width > self.img_height
+    }
 }
 
 impl OcrBlock {
@@ -172,18 +618,66 @@ impl OcrBlock {
         top: f64, left: f64, bottom: f64, right: f64,
         font_size: u32, vertical: bool,
     ) -> Self {
-        let uuid = {
-            let ts = uuid::Timestamp::now(uuid::NoContext);
-            uuid::Uuid::new_v7(ts).simple().to_string().into()
-        };
+        let uuid = block_uuid();
         let box_ = (left as u32, top as u32, right as u32, bottom as u32);
-        Self { uuid, box_, vertical, font_size, lines: Vec::default() }
+        let (lines, lines_coords, extra) = Default::default();
+        Self { uuid, box_, vertical, font_size, lines, lines_coords, extra }
     }
 
     pub fn validate(&self) -> bool {
         self.box_.0 <= self.box_.2 && self.box_.1 <= self.box_.3
     }
 
+    /// Moves and/or resizes the box of this block. The line coordinates
+    /// are transformed so that they keep their position relative to the box.
+    pub fn set_box(&mut self, box_: (u32, u32, u32, u32)) {
+        let (width, height) = (self.width(), self.height());
+        let (left, top) = (self.left(), self.top());
+        self.box_ = box_;
+        let scale_x = if width > 0. { self.width() / width } else { 1. };
+        let scale_y = if height > 0. { self.height() / height } else { 1. };
+        for (x, y) in self.lines_coords.iter_mut().flatten() {
+            *x = self.box_.0 as f64 + (*x - left) * scale_x;
+            *y = self.box_.1 as f64 + (*y - top) * scale_y;
+        }
+    }
+
+    /// Fits the box of this block to its lines as they are displayed, i.e. autosizing it.
+    /// The lines do not move, so the line coordinates are only clamped to the new box,
+    /// so that they remain within it.
+    pub fn fit_box(&mut self, box_: (u32, u32, u32, u32)) {
+        self.box_ = box_;
+        let (left, top, right, bottom) = (box_.0 as f64, box_.1 as f64, box_.2 as f64, box_.3 as f64);
+        for (x, y) in self.lines_coords.iter_mut().flatten() {
+            *x = x.max(left).min(right);
+            *y = y.max(top).min(bottom);
+        }
+    }
+
+    /// Sets the lines of this block. The line coordinates are discarded if they
+    /// no longer correspond to the lines, i.e. if lines were added or removed.
+    pub fn set_lines(&mut self, lines: Vec<AttrValue>) {
+        if lines.len() != self.lines.len() {
+            self.lines_coords.clear();
+        }
+        self.lines = lines;
+    }
+
+    /// The bounding box (left, top, right, bottom) of each line, in image pixels.
+    /// This is None if the line coordinates are missing or incomplete.
+    pub fn line_bounds(&self) -> Option<Vec<(f64, f64, f64, f64)>> {
+        if self.lines.is_empty() || self.lines_coords.len() != self.lines.len() {
+            return None;
+        }
+        self.lines_coords.iter().map(|points| {
+            let first = points.first()?;
+            Some(points.iter().fold(
+                (first.0, first.1, first.0, first.1),
+                |(l, t, r, b), (x, y)| (l.min(*x), t.min(*y), r.max(*x), b.max(*y)),
+            ))
+        }).collect()
+    }
+
     pub fn top(&self) -> f64 { self.box_.1 as f64 }
     pub fn left(&self) -> f64 { self.box_.0 as f64 }
     pub fn height(&self) -> f64 { self.box_.3.saturating_sub(self.box_.1) as f64 }
@@ -233,3 +727,81 @@ impl From<PageImage> for gloo_file::ObjectUrl {
         page_image.inner.into()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block() -> OcrBlock {
+        let lines_coords = vec![vec![(10., 10.), (20., 10.), (20., 50.), (10., 50.)]];
+        OcrBlock { box_: (0, 0, 100, 100), lines: vec!["a".into()], lines_coords, ..OcrBlock::default() }
+    }
+
+    fn volume(pages: usize, reader_state: ReaderState) -> VolumeMetadata {
+        let pages = (0..pages).map(|i| (i.to_string().into(), AttrValue::default())).collect();
+        VolumeMetadata { pages, reader_state, wide_pages: Some(vec![3]), ..VolumeMetadata::default() }
+    }
+
+    #[test]
+    fn pairs_pages_into_spreads() {
+        let spreads = |page_mode, pairing_breaks| {
+            volume(6, ReaderState { page_mode, pairing_breaks, ..ReaderState::default() }).spreads()
+        };
+        assert_eq!(spreads(PageMode::Double, vec![]), [(0, None), (1, Some(2)), (3, Some(4)), (5, None)]);
+        assert_eq!(spreads(PageMode::Single, vec![]), (0..6).map(|i| (i, None)).collect::<Vec<_>>());
+        assert_eq!(spreads(PageMode::Auto, vec![]), [(0, None), (1, Some(2)), (3, None), (4, Some(5))]);
+        assert_eq!(
+            spreads(PageMode::Double, vec![PairingBreak::Blank(2)]),
+            [(0, None), (1, None), (2, Some(3)), (4, Some(5))],
+        );
+        assert_eq!(
+            spreads(PageMode::Double, vec![PairingBreak::Single(1)]),
+            [(0, None), (1, None), (2, Some(3)), (4, Some(5))],
+        );
+    }
+
+    #[test]
+    fn selects_the_pages_of_the_current_spread() {
+        let pages = |current_page, direction| {
+            let state = ReaderState { current_page, direction, ..ReaderState::default() };
+            let (left, right) = volume(6, state).select_pages();
+            (left.map(|p| p.to_string()), right.map(|p| p.to_string()))
+        };
+        let page = |i: usize| Some(i.to_string());
+        assert_eq!(pages(0, ReadingDirection::RightToLeft), (None, page(0)));
+        assert_eq!(pages(2, ReadingDirection::RightToLeft), (page(2), page(1)));
+        assert_eq!(pages(2, ReadingDirection::LeftToRight), (page(1), page(2)));
+    }
+
+    #[test]
+    fn keeps_the_progress_and_settings_of_the_existing_copy() {
+        let mut existing = volume(6, ReaderState { current_page: 5, ..ReaderState::default() });
+        existing.title = "existing".into();
+        existing.hide_sidebar = true;
+        existing.line_height = 1.5;
+        existing.magnifier.zoom = 300;
+        existing.bookmarks = vec![PageMarker { page: 1, label: None }, PageMarker { page: 5, label: None }];
+
+        let mut upload = volume(4, ReaderState::default());
+        upload.title = "upload".into();
+        upload.keep_progress(&existing);
+        assert_eq!(upload.title, "upload");
+        assert!(upload.hide_sidebar && upload.line_height == 1.5 && upload.magnifier == existing.magnifier);
+        assert_eq!(upload.reader_state.current_page, 3);
+        assert_eq!(upload.bookmarks.iter().map(|m| m.page).collect::<Vec<_>>(), [1]);
+    }
+
+    #[test]
+    fn set_box_transforms_the_lines() {
+        let mut block = block();
+        block.set_box((100, 0, 150, 200));
+        assert_eq!(block.lines_coords[0], [(105., 20.), (110., 20.), (110., 100.), (105., 100.)]);
+    }
+
+    #[test]
+    fn fit_box_keeps_the_lines_within_the_box() {
+        let mut block = block();
+        block.fit_box((10, 10, 18, 40));
+        assert_eq!(block.lines_coords[0], [(10., 10.), (18., 10.), (18., 40.), (10., 40.)]);
+    }
+}
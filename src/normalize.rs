@@ -0,0 +1,211 @@
+use enclose::enclose;
+use rexie::Rexie;
+use std::rc::Rc;
+use web_sys::MouseEvent;
+use yew::{html, AttrValue, Callback, Component, Context, Html, Properties};
+
+use crate::models::{PageOcr, VolumeId};
+use crate::notify::{Notification, Notification::Warning};
+use crate::utils::db::{get_volume, get_volume_ocr};
+use crate::utils::normalize::{apply_changes, normalize_pages, summarize, Change, Normalization, Summary};
+
+/// The maximum number of changed blocks which are listed.
+const MAX_LISTED: usize = 200;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub db: Rc<Rexie>,
+    pub notify: Callback<Notification>,
+    pub volume_id: VolumeId,
+    pub close_modal: Callback<MouseEvent>,
+    /// The OCR of a page which has not been written yet, i.e. the queued edits of the reader.
+    #[prop_or_default]
+    pub latest: Option<Callback<AttrValue, Option<PageOcr>>>,
+    /// Receives the changes instead of them being written, i.e. so that the reader can
+    /// record them as an edit which can be undone, alongside the callback which
+    /// receives the number of changes which were applied.
+    #[prop_or_default]
+    pub commit: Option<Callback<(Vec<Change>, Callback<usize>)>>,
+}
+
+pub enum Message {
+    Loaded(Vec<(usize, AttrValue, PageOcr)>),
+    Toggle(Normalization),
+    Apply,
+    Applied(usize),
+    Notify(Notification),
+}
+
+enum State {
+    Loading,
+    Ready,
+    Applying,
+    Applied(usize),
+}
+
+/// NormalizeModal creates a modal overlay where users choose which normalizations
+/// (see utils::normalize) to apply to the text of every block of a volume,
+/// and review a summary of the changes before they are applied.
+pub struct NormalizeModal {
+    state: State,
+    normalizations: Vec<Normalization>,
+    pages: Vec<(usize, AttrValue, PageOcr)>,
+    changes: Vec<Change>,
+    summary: Summary,
+    apply: Callback<MouseEvent>,
+    cancel_click: Callback<MouseEvent>,
+}
+
+impl Component for NormalizeModal {
+    type Message = Message;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let Props { db, volume_id, latest, .. } = ctx.props();
+        ctx.link().send_future(enclose!((db, volume_id, latest) load(db, volume_id, latest)));
+        let apply = ctx.link().callback(|_| Message::Apply);
+        let cancel_click = Callback::from(|e: MouseEvent| e.stop_propagation());
+        Self {
+            state: State::Loading,
+            normalizations: Normalization::ALL.into_iter().filter(Normalization::default_enabled).collect(),
+            pages: vec![],
+            changes: vec![],
+            summary: Summary::default(),
+            apply,
+            cancel_click,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        let Props { db, notify, volume_id, commit, .. } = ctx.props();
+        match msg {
+            Message::Loaded(pages) => {
+                self.state = State::Ready;
+                self.pages = pages;
+                self.preview();
+                true
+            }
+            Message::Toggle(normalization) => {
+                match self.normalizations.iter().position(|n| *n == normalization) {
+                    Some(index) => { self.normalizations.remove(index); }
+                    None => {
+                        // The normalizations are applied in the order they are listed.
+                        self.normalizations.push(normalization);
+                        self.normalizations.sort_by_key(|n| Normalization::ALL.iter().position(|m| m == n));
+                    }
+                }
+                self.preview();
+                true
+            }
+            Message::Apply => {
+                if !matches!(self.state, State::Ready) || self.changes.is_empty() { return false; }
+                let changes = std::mem::take(&mut self.changes);
+                self.state = State::Applying;
+                if let Some(commit) = commit {
+                    commit.emit((changes, ctx.link().callback(Message::Applied)));
+                    return true;
+                }
+                ctx.link().send_future(enclose!((db, volume_id) async move {
+                    match apply_changes(&db, volume_id, &changes).await {
+                        Ok(count) => Message::Applied(count),
+                        Err(err) => Message::Notify(Warning("failed to normalize the text of the volume", err.to_string())),
+                    }
+                }));
+                true
+            }
+            Message::Applied(count) => {
+                self.state = State::Applied(count);
+                true
+            }
+            Message::Notify(notification) => {
+                self.state = State::Ready;
+                self.preview();
+                notify.emit(notification);
+                true
+            }
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let Props { close_modal, .. } = ctx.props();
+        let Summary { pages, blocks, lines, counts } = &self.summary;
+        let content = match self.state {
+            State::Loading => html! { <p>{"Reading the text of the volume..."}</p> },
+            State::Applying => html! { <p>{"Applying..."}</p> },
+            State::Applied(count) => html! {
+                <p>{format!("Normalized {count} textbox(es)")}</p>
+            },
+            State::Ready => html! {<>
+                <div id="NormalizeOptions">
+                    {for Normalization::ALL.into_iter().map(|normalization| {
+                        let checked = self.normalizations.contains(&normalization);
+                        let onchange = ctx.link().callback(move |_| Message::Toggle(normalization));
+                        html! {
+                            <label>
+                                <input type="checkbox" {checked} {onchange}/>{normalization.label()}
+                            </label>
+                        }
+                    })}
+                </div>
+                <p id="NormalizeSummary">
+                    {format!("{lines} line(s) will change, in {blocks} textbox(es) across {pages} page(s)")}
+                    {for counts.iter().filter(|(_, count)| *count > 0).map(|(normalization, count)| html! {
+                        <span>{format!("{}: {count} line(s)", normalization.label())}</span>
+                    })}
+                </p>
+                <div id="NormalizeChanges">
+                    {for self.changes.iter().take(MAX_LISTED).map(|change| html! {
+                        <div class="normalize-change">
+                            <span class="normalize-page">{format!("p. {}", change.page + 1)}</span>
+                            <del>{change.before.lines.join("\n")}</del>
+                            <ins>{change.after.lines.join("\n")}</ins>
+                        </div>
+                    })}
+                    if self.changes.len() > MAX_LISTED {
+                        <p>{format!("and {} more textbox(es)", self.changes.len() - MAX_LISTED)}</p>
+                    }
+                </div>
+                <button onclick={&self.apply} disabled={self.changes.is_empty()}>{"Apply"}</button>
+            </>},
+        };
+        html! {
+            <div id="Modal" onclick={close_modal}>
+                <div class="modal-content" onclick={&self.cancel_click}>
+                    <div class="close-symbol" onclick={close_modal}>{crate::icons::close()}</div>
+                    <p class="modal-title">{"Normalize Text"}</p>
+                    <p class="modal-note">
+                        {"Makes the text of every textbox of the volume consistent. "}
+                        {"Review the changes below before applying them."}
+                    </p>
+                    <div id="NormalizeContent">{content}</div>
+                </div>
+            </div>
+        }
+    }
+}
+
+impl NormalizeModal {
+    fn preview(&mut self) {
+        self.changes = normalize_pages(&self.pages, &self.normalizations);
+        self.summary = summarize(&self.changes, &self.normalizations);
+    }
+}
+
+async fn load(
+    db: Rc<Rexie>, volume_id: VolumeId, latest: Option<Callback<AttrValue, Option<PageOcr>>>,
+) -> Message {
+    let result = match get_volume(&db, volume_id).await {
+        Ok(volume) => get_volume_ocr(&db, &volume).await.map(|ocr| (volume, ocr)),
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok((volume, ocr)) => {
+            let pages = volume.pages.iter().zip(ocr).enumerate().map(|(page, ((page_name, _), ocr))| {
+                let ocr = latest.as_ref().and_then(|latest| latest.emit(page_name.clone())).unwrap_or(ocr);
+                (page, page_name.clone(), ocr)
+            }).collect();
+            Message::Loaded(pages)
+        }
+        Err(err) => Message::Notify(Warning("failed to retrieve Ocr data from IndexedDB", err.to_string())),
+    }
+}
@@ -2,18 +2,28 @@ use std::rc::Rc;
 
 use enclose::enclose;
 use rexie::Rexie;
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsCast;
 use web_sys::{Event, KeyboardEvent, MouseEvent};
-use yew::{html, Callback, Component, Context, Html, NodeRef, Properties};
+use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties};
+use yew_router::scope_ext::RouterScopeExt;
 
-use crate::models::{VolumeId, VolumeMetadata};
+use crate::models::{BlockConflict, PageOcr, ReadingDirection, VolumeId, VolumeMetadata};
+use crate::normalize::NormalizeModal;
 use crate::notify::{Notification, Notification::Warning as Warning};
+use crate::reader::history::{BlockEdit, Edit, History};
 use crate::reader::window::{Rect, WindowState};
+use crate::Route;
 use crate::utils::{
-    db::{get_volume, put_volume},
+    db::{get_ocr, get_volume, put_volume},
+    normalize::Change,
     timestamp,
     web::{focus, focused_element, window},
 };
 
+const CHAPTER_PROMPT: &str = "Mark this page as the start of a chapter.\nChapter name (optional):";
+const REMOVE_CHAPTER_PROMPT: &str = "Remove the chapter marker from this page?";
+
 #[derive(Default)]
 pub struct Cursor {
     pub magnify: bool,
@@ -28,40 +38,103 @@ pub struct ReaderProps {
     pub volume_id: VolumeId,
 }
 
+/// The query string of the reader, which opens the reader at a given page
+/// with one of its blocks highlighted, e.g. /volume/1/reader?page=12&block=<uuid>.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct ReaderQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub block: Option<AttrValue>,
+}
+
 pub enum ReaderMessage {
     Set(Box<VolumeMetadata>),
     Notify(Notification),
     Commit(sidebar::SidebarData),
     Focus,
+    GoToPage(usize),
+    GoToBlock(usize, AttrValue),
+    ApplyEdit(Edit),
+    /// The changes of the normalize modal, and the callback which receives the number applied.
+    Normalize(Vec<Change>, Callback<usize>),
+    /// The part of an edit which could be applied, which is recorded so that it can be undone,
+    /// and the error of any page which could not be edited.
+    Applied(Edit, Option<Notification>),
+    /// Opens the panel (or closes it if it is already open), and None closes any open panel.
+    PanelToggle(Option<EditPanel>),
+    BookmarkToggle,
+    ChapterToggle,
     HelpToggle,
     MagnifierToggle,
     MutableToggle,
+    PairingBreakToggle,
+    ResolveConflict(usize, bool),
+    /// Their version of a conflict was taken (the edit), or the block had changed since
+    /// the merge (the conflict updated to the current block).
+    ConflictResolved(Box<BlockConflict>, Result<Edit, Box<BlockConflict>>),
     SidebarToggle,
     NextPage,
     PrevPage,
+    Record(Edit),
+    Undo,
+    Redo,
+    Refresh,
     Resize(bool),
+    SaveStatus(queue::SaveStatus),
     UpdateCursor(i32, i32),
 }
 
+/// The panels which can be opened alongside the pages while editing.
+#[derive(Clone, Copy, PartialEq)]
+pub enum EditPanel {
+    Find,
+    Lint,
+    Normalize,
+}
+
 pub struct Reader {
+    cache: cache::PageCache,
+    /// The pages which were last prefetched, i.e. the spreads adjacent to the current spread.
+    prefetched: Vec<AttrValue>,
+    queue: queue::OcrQueue,
+    save_status: Option<queue::SaveStatus>,
     cursor: Cursor,
+    /// The panel which is open, which is only possible while editing.
+    panel: Option<EditPanel>,
+    /// The block which is highlighted, i.e. the result of a search, until the page changes.
+    highlight: Option<AttrValue>,
+    history: History,
     mutable: bool,
     node: NodeRef,
     node_left: NodeRef,
     node_right: NodeRef,
+    revision: u64,
     volume: Option<VolumeMetadata>,
     window: WindowState,
     show_help: bool,
     sidebar_expanded: bool,
+    /// The query the reader was opened with, which is applied once the volume is loaded.
+    target: Option<ReaderQuery>,
 
     commit_sidebar_data: Callback<sidebar::SidebarData>,
     focus: Callback<()>,
+    go_to_page: Callback<usize>,
+    apply_edit: Callback<Edit>,
+    close_panel: Callback<MouseEvent>,
+    go_to_block: Callback<(usize, AttrValue)>,
+    latest_ocr: Callback<AttrValue, Option<PageOcr>>,
+    normalize: Callback<(Vec<Change>, Callback<usize>)>,
+    handle_keydown: Callback<KeyboardEvent>,
     handle_keypress: Callback<KeyboardEvent>,
     handle_image_load: Callback<Event>,
     handle_right_click: Callback<MouseEvent>,
+    record_edit: Callback<Edit>,
+    resolve_conflict: Callback<(usize, bool)>,
     toggle_sidebar: Callback<MouseEvent>,
     update_cursor: Callback<MouseEvent>,
     _resize_listener: gloo_events::EventListener,
+    _unload_listener: gloo_events::EventListener,
 }
 
 impl Component for Reader {
@@ -78,15 +151,57 @@ impl Component for Reader {
                 move |_: &Event| link.send_message(Self::Message::Resize(false)),
             )
         };
+        let queue = queue::OcrQueue::new(
+            ctx.props().db.clone(), ctx.link().callback(Self::Message::SaveStatus),
+        );
+        // Queued OCR is written before the page is closed, and the user is asked
+        // to stay on the page if it cannot be written in time.
+        let _unload_listener = {
+            let queue = queue.clone();
+            gloo_events::EventListener::new_with_options(
+                &window(),
+                "beforeunload",
+                gloo_events::EventListenerOptions::enable_prevent_default(),
+                move |e: &Event| {
+                    queue.flush();
+                    if !queue.is_idle() { e.prevent_default(); }
+                },
+            )
+        };
 
         let commit_sidebar_data = ctx.link().callback(Self::Message::Commit);
         let focus = ctx.link().callback(|()| Self::Message::Focus);
+        let handle_keydown = ctx.link().batch_callback(
+            |e: KeyboardEvent| {
+                // Undo & Redo are handled on keydown, as browsers do not
+                // consistently fire keypress events when Ctrl is held.
+                if !(e.ctrl_key() || e.meta_key()) || is_text_input(&e) {
+                    return None;
+                }
+                let message = match (e.code().as_str(), e.shift_key()) {
+                    ("KeyZ", false) => Self::Message::Undo,
+                    ("KeyZ", true) | ("KeyY", false) => Self::Message::Redo,
+                    _ => return None,
+                };
+                e.prevent_default();
+                Some(message)
+            }
+        );
         let handle_keypress = ctx.link().batch_callback(
             |e: KeyboardEvent| {
                 // gloo_console::log!("KeyCode:", e.code());
+                if e.ctrl_key() || e.meta_key() || is_text_input(&e) {
+                    return None;
+                }
                 match e.code().as_str() {
+                    "KeyB" => Some(Self::Message::PairingBreakToggle),
+                    "KeyC" => Some(Self::Message::ChapterToggle),
                     "KeyE" => Some(Self::Message::MutableToggle),
+                    "KeyF" => Some(Self::Message::PanelToggle(Some(EditPanel::Find))),
                     "KeyH" => Some(Self::Message::HelpToggle),
+                    "KeyL" => Some(Self::Message::PanelToggle(Some(EditPanel::Lint))),
+                    "KeyM" => Some(Self::Message::BookmarkToggle),
+                    "KeyN" => Some(Self::Message::PanelToggle(Some(EditPanel::Normalize))),
                     "KeyS" => Some(Self::Message::SidebarToggle),
                     "KeyX" => Some(Self::Message::PrevPage),
                     "KeyZ" => Some(Self::Message::NextPage),
@@ -103,6 +218,21 @@ impl Component for Reader {
             e.prevent_default();
             Self::Message::MagnifierToggle
         });
+        let go_to_page = ctx.link().callback(Self::Message::GoToPage);
+        let apply_edit = ctx.link().callback(Self::Message::ApplyEdit);
+        let close_panel = ctx.link().callback(|_| Self::Message::PanelToggle(None));
+        let go_to_block = ctx.link().callback(
+            |(page, block)| Self::Message::GoToBlock(page, block)
+        );
+        let latest_ocr = {
+            let (queue, volume_id) = (queue.clone(), ctx.props().volume_id);
+            Callback::from(move |page: AttrValue| queue.latest(volume_id, &page))
+        };
+        let normalize = ctx.link().callback(|(changes, reply)| Self::Message::Normalize(changes, reply));
+        let record_edit = ctx.link().callback(Self::Message::Record);
+        let resolve_conflict = ctx.link().callback(
+            |(index, take_theirs)| Self::Message::ResolveConflict(index, take_theirs)
+        );
         let toggle_sidebar = ctx.link().callback(|e: MouseEvent| {
             e.prevent_default();
             Self::Message::SidebarToggle
@@ -111,26 +241,49 @@ impl Component for Reader {
             |e: MouseEvent| Self::Message::UpdateCursor(e.x(), e.y())
         );
 
+        let target = ctx.link().location()
+            .and_then(|location| location.query::<ReaderQuery>().ok())
+            .filter(|query| query != &ReaderQuery::default());
+
         let cursor = Cursor::default();
         let window = WindowState::default();
         Self {
             cursor,
+            panel: None,
+            highlight: None,
+            history: History::default(),
             mutable: false,
             node: NodeRef::default(),
             node_left: NodeRef::default(),
             node_right: NodeRef::default(),
+            revision: 0,
+            cache: cache::PageCache::default(),
+            prefetched: vec![],
+            queue,
+            save_status: None,
             volume: None,
             window,
             show_help: false,
             sidebar_expanded: false,
+            target,
             commit_sidebar_data,
             focus,
+            go_to_page,
+            apply_edit,
+            close_panel,
+            go_to_block,
+            latest_ocr,
+            normalize,
+            handle_keydown,
             handle_keypress,
             handle_image_load,
             handle_right_click,
+            record_edit,
+            resolve_conflict,
             toggle_sidebar,
             update_cursor,
             _resize_listener,
+            _unload_listener,
         }
     }
 
@@ -145,15 +298,45 @@ impl Component for Reader {
             focus(&self.node);
         }
 
+        if let Some(volume) = &self.volume {
+            let adjacent = volume.adjacent_pages();
+            if adjacent != self.prefetched {
+                // The page has changed, so the edits of the previous page are written.
+                self.queue.flush();
+                self.prefetched = adjacent.clone();
+                let ReaderProps { db, volume_id, .. } = ctx.props();
+                let prefetch = self.cache.clone().prefetch(db.clone(), *volume_id, adjacent);
+                wasm_bindgen_futures::spawn_local(prefetch);
+            }
+        }
+
         // On every rerender, check to see if the image proportions has changed.
         ctx.link().send_message(Self::Message::Resize(false));
     }
 
+    fn destroy(&mut self, _ctx: &Context<Self>) {
+        // The queue writes the remaining OCR after the reader is closed.
+        self.queue.flush();
+    }
+
     fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
-        let ReaderProps { db, .. } = ctx.props();
+        let ReaderProps { db, volume_id, .. } = ctx.props();
         match msg {
             ReaderMessage::Set(volume) => {
                 let previous = self.volume.replace(*volume);
+                if let (Some(target), Some(volume)) = (self.target.take(), &mut self.volume) {
+                    self.highlight = target.block;
+                    if let Some(page) = target.page.filter(|page| *page < volume.pages.len()) {
+                        volume.go_to_page(page);
+                        ctx.link().send_future(
+                            enclose!((db, volume) Self::commit_volume(db, volume))
+                        );
+                    }
+                    // The query is removed, so that reloading the reader does not return to the target.
+                    if let Some(navigator) = ctx.link().navigator() {
+                        navigator.replace(&Route::Reader { volume_id: *volume_id });
+                    }
+                }
                 previous != self.volume
             }
             ReaderMessage::Notify(notification) => {
@@ -169,6 +352,8 @@ impl Component for Reader {
                     magnifier_width,
                     magnifier_radius,
                     magnification,
+                    page_mode,
+                    right_to_left,
                     show_help,
                     show_magnifier,
                 } = data;
@@ -187,7 +372,18 @@ impl Component for Reader {
                     volume.magnifier.width = magnifier_width;
                     volume.magnifier.radius = magnifier_radius;
                     volume.magnifier.zoom = magnification;
-                    volume.reader_state.first_page_is_cover = first_page_is_cover;
+                    if volume.reader_state.first_page_is_cover != first_page_is_cover {
+                        volume.reader_state.first_page_is_cover = first_page_is_cover;
+                        volume.go_to_page(volume.reader_state.current_page);
+                    }
+                    volume.reader_state.direction = if right_to_left {
+                        ReadingDirection::RightToLeft
+                    } else { ReadingDirection::LeftToRight };
+                    if volume.reader_state.page_mode != page_mode {
+                        // Changing the page mode changes how pages are paired.
+                        volume.reader_state.page_mode = page_mode;
+                        volume.go_to_page(volume.reader_state.current_page);
+                    }
                     ctx.link().send_future(
                         enclose!((db, volume) Self::commit_volume(db, volume))
                     );
@@ -198,6 +394,95 @@ impl Component for Reader {
                 focus(&self.node);
                 false
             }
+            ReaderMessage::GoToPage(index) => {
+                self.highlight = None;
+                if let Some(volume) = &mut self.volume {
+                    volume.go_to_page(index);
+                    ctx.link().send_future(
+                        enclose!((db, volume) Self::commit_volume(db, volume))
+                    );
+                }
+                true
+            }
+            ReaderMessage::GoToBlock(index, block) => {
+                if let Some(volume) = &mut self.volume {
+                    volume.go_to_page(index);
+                    ctx.link().send_future(
+                        enclose!((db, volume) Self::commit_volume(db, volume))
+                    );
+                }
+                self.highlight = Some(block);
+                true
+            }
+            ReaderMessage::Normalize(changes, reply) => {
+                let edit = Edit(changes.into_iter().map(|change| BlockEdit {
+                    page: change.page_name,
+                    index: change.index,
+                    before: Some(change.before),
+                    after: Some(change.after),
+                }).collect());
+                let (cache, queue) = (self.cache.clone(), self.queue.clone());
+                ctx.link().send_future(enclose!((db, volume_id) async move {
+                    let (applied, error) = Self::apply_edit(db, volume_id, cache, queue, edit).await;
+                    reply.emit(applied.0.len());
+                    ReaderMessage::Applied(applied, error)
+                }));
+                false
+            }
+            ReaderMessage::ApplyEdit(edit) => {
+                let (cache, queue) = (self.cache.clone(), self.queue.clone());
+                ctx.link().send_future(enclose!((db, volume_id) async move {
+                    let (applied, error) = Self::apply_edit(db, volume_id, cache, queue, edit).await;
+                    ReaderMessage::Applied(applied, error)
+                }));
+                false
+            }
+            ReaderMessage::Applied(edit, error) => {
+                self.history.record_separately(edit);
+                if let Some(notification) = error {
+                    ctx.props().notify.emit(notification);
+                }
+                self.revision = timestamp();
+                true
+            }
+            ReaderMessage::PanelToggle(panel) => {
+                if panel.is_some() && !self.mutable { return false; }
+                self.panel = if self.panel == panel { None } else { panel };
+                if self.panel.is_none() {
+                    focus(&self.node);
+                }
+                true
+            }
+            ReaderMessage::BookmarkToggle => {
+                if let Some(volume) = &mut self.volume {
+                    volume.toggle_bookmark(volume.reader_state.current_page);
+                    ctx.link().send_future(
+                        enclose!((db, volume) Self::commit_volume(db, volume))
+                    );
+                }
+                true
+            }
+            ReaderMessage::ChapterToggle => {
+                if let Some(volume) = &mut self.volume {
+                    let current_page = volume.reader_state.current_page;
+                    if volume.has_chapter(current_page) {
+                        if !gloo_dialogs::confirm(REMOVE_CHAPTER_PROMPT) { return false; }
+                        volume.remove_chapter(current_page);
+                    } else {
+                        let Some(label) = gloo_dialogs::prompt(CHAPTER_PROMPT, None) else {
+                            return false;
+                        };
+                        let label = Some(label.trim().to_owned())
+                            .filter(|label| !label.is_empty())
+                            .map(AttrValue::from);
+                        volume.add_chapter(current_page, label);
+                    }
+                    ctx.link().send_future(
+                        enclose!((db, volume) Self::commit_volume(db, volume))
+                    );
+                }
+                true
+            }
             ReaderMessage::HelpToggle => {
                 self.show_help = !self.show_help;
                 true
@@ -208,6 +493,59 @@ impl Component for Reader {
             }
             ReaderMessage::MutableToggle => {
                 self.mutable = !self.mutable;
+                if !self.mutable { self.panel = None; }
+                true
+            }
+            ReaderMessage::PairingBreakToggle => {
+                if let Some(volume) = &mut self.volume {
+                    let current_page = volume.reader_state.current_page;
+                    volume.reader_state.toggle_pairing_break(current_page);
+                    volume.go_to_page(current_page);
+                    ctx.link().send_future(
+                        enclose!((db, volume) Self::commit_volume(db, volume))
+                    );
+                }
+                true
+            }
+            ReaderMessage::ResolveConflict(index, take_theirs) => {
+                let Some(volume) = &mut self.volume else { return false; };
+                let Some(conflict) = volume.conflicts.get(index).cloned() else { return false; };
+                match (take_theirs, volume.pages.get(conflict.page).map(|(page, _)| page.clone())) {
+                    // The conflict is only removed once their version has been applied.
+                    (true, Some(page)) => {
+                        volume.go_to_page(conflict.page);
+                        ctx.link().send_future(enclose!((db, volume_id, self.cache => cache, self.queue => queue) async move {
+                            Self::take_theirs(db, volume_id, cache, queue, page, conflict).await
+                        }));
+                    }
+                    _ => { volume.conflicts.remove(index); }
+                }
+                ctx.link().send_future(
+                    enclose!((db, volume) Self::commit_volume(db, volume))
+                );
+                true
+            }
+            ReaderMessage::ConflictResolved(conflict, result) => {
+                let Some(volume) = &mut self.volume else { return false; };
+                let Some(index) = volume.conflicts.iter().position(|c| c == conflict.as_ref()) else { return false; };
+                match result {
+                    Ok(edit) => {
+                        volume.conflicts.remove(index);
+                        // Taking their version is recorded as an edit, so that it can be undone.
+                        self.history.record_separately(edit);
+                        self.revision = timestamp();
+                    }
+                    Err(rebased) => {
+                        volume.conflicts[index] = *rebased;
+                        ctx.props().notify.emit(Warning(
+                            "the textbox has changed since the merge, resolve the conflict again",
+                            format!("conflict on page {}", conflict.page + 1),
+                        ));
+                    }
+                }
+                ctx.link().send_future(
+                    enclose!((db, volume) Self::commit_volume(db, volume))
+                );
                 true
             }
             ReaderMessage::SidebarToggle => {
@@ -218,6 +556,7 @@ impl Component for Reader {
                 true
             }
             ReaderMessage::NextPage => {
+                self.highlight = None;
                 if let Some(volume) = &mut self.volume {
                     volume.page_forward();
                     ctx.link().send_future(
@@ -227,6 +566,7 @@ impl Component for Reader {
                 true
             }
             ReaderMessage::PrevPage => {
+                self.highlight = None;
                 if let Some(volume) = &mut self.volume {
                     volume.page_backward();
                     ctx.link().send_future(
@@ -235,6 +575,44 @@ impl Component for Reader {
                 }
                 true
             }
+            ReaderMessage::Record(edit) => {
+                self.history.record(edit);
+                false
+            }
+            ReaderMessage::Undo | ReaderMessage::Redo => {
+                if !self.mutable { return false; }
+                let edit = match msg {
+                    ReaderMessage::Undo => self.history.undo(),
+                    _ => self.history.redo(),
+                };
+                let Some(edit) = edit else { return false; };
+
+                // If the edited page is not currently displayed, then move to it
+                // so that the change being undone (or redone) is visible.
+                if let (Some(volume), Some(page)) = (&mut self.volume, edit.page()) {
+                    let (left, right) = volume.select_pages();
+                    if left.as_ref() != Some(page) && right.as_ref() != Some(page) {
+                        if let Some(index) = volume.page_index(page) {
+                            volume.go_to_page(index);
+                            ctx.link().send_future(
+                                enclose!((db, volume) Self::commit_volume(db, volume))
+                            );
+                        }
+                    }
+                }
+                let (cache, queue) = (self.cache.clone(), self.queue.clone());
+                ctx.link().send_future(enclose!((db, volume_id) async move {
+                    match Self::apply_edit(db, volume_id, cache, queue, edit).await {
+                        (_, Some(notification)) => ReaderMessage::Notify(notification),
+                        (_, None) => ReaderMessage::Refresh,
+                    }
+                }));
+                true
+            }
+            ReaderMessage::Refresh => {
+                self.revision = timestamp();
+                true
+            }
             ReaderMessage::Resize(force) => {
                 let left = Rect::try_from(&self.node_left).unwrap_or(self.window.left.rect);
                 let right = Rect::try_from(&self.node_right).unwrap_or(self.window.right.rect);
@@ -245,6 +623,15 @@ impl Component for Reader {
                 }
                 false
             }
+            ReaderMessage::SaveStatus(status) => {
+                // The failed writes are retried, so only the first failure is reported.
+                let failing = matches!(self.save_status, Some(queue::SaveStatus::Failed(_)));
+                if let (queue::SaveStatus::Failed(err), false) = (&status, failing) {
+                    ctx.props().notify.emit(Warning("failed to save OCR", err.clone()));
+                }
+                self.save_status = Some(status);
+                true
+            }
             ReaderMessage::UpdateCursor(x, y) => {
                 self.cursor.position = (x, y);
                 self.cursor.magnify
@@ -255,14 +642,34 @@ impl Component for Reader {
     fn view(&self, ctx: &Context<Self>) -> Html {
         if let Some(volume) = &self.volume {
             let ReaderProps { db, notify, volume_id, .. } = ctx.props();
-            let (first_page, second_page) = volume.select_pages();
+            let (left_page, right_page) = volume.select_pages();
+            let spread: Vec<AttrValue> = left_page.iter().chain(right_page.iter()).cloned().collect();
+            // The left pagebar moves towards the end of a right-to-left volume.
+            let next_page = ctx.link().callback(|_| Self::Message::NextPage);
+            let prev_page = ctx.link().callback(|_| Self::Message::PrevPage);
+            let (left_bar, right_bar) = match volume.reader_state.direction {
+                ReadingDirection::RightToLeft => (next_page, prev_page),
+                ReadingDirection::LeftToRight => (prev_page, next_page),
+            };
+            let pagebar_height = if right_page.is_some() {
+                self.window.right.rect.height
+            } else { self.window.left.rect.height } as u32;
             let magnifier = if self.cursor.magnify {
                 volume.magnifier.render(&self.cursor.position, &self.node_left, &self.node_right)
             } else { Html::default() };
             return html! {
-            <div id="ReaderGrid" tabindex={"-1"} onkeypress={&self.handle_keypress}>
+            <div
+              id="ReaderGrid" tabindex={"-1"}
+              onkeydown={&self.handle_keydown}
+              onkeypress={&self.handle_keypress}
+            >
                 <sidebar::Sidebar
                   commit={&self.commit_sidebar_data}
+                  go_to_page={&self.go_to_page}
+                  bookmarks={volume.bookmarks.clone()}
+                  chapters={volume.chapters.clone()}
+                  conflicts={volume.conflicts.clone()}
+                  resolve_conflict={&self.resolve_conflict}
                   onblur={&self.focus}
                   toggle_sidebar={&self.toggle_sidebar}
                   expanded={self.sidebar_expanded}
@@ -274,6 +681,8 @@ impl Component for Reader {
                     magnifier_height: volume.magnifier.height,
                     magnifier_radius: volume.magnifier.radius,
                     magnification: volume.magnifier.zoom,
+                    page_mode: volume.reader_state.page_mode,
+                    right_to_left: volume.reader_state.direction == ReadingDirection::RightToLeft,
                     show_help: self.show_help,
                     show_magnifier: self.cursor.magnify,
                   }}
@@ -287,47 +696,87 @@ impl Component for Reader {
                   oncontextmenu={&self.handle_right_click}
                   onmousemove={&self.update_cursor}
                 >
-                {pagebar(
-                    self.window.right.rect.height as u32,
-                    ctx.link().callback(|_| Self::Message::PrevPage),
-                )}
+                {pagebar(pagebar_height, left_bar)}
 
                 {magnifier}
 
-                if let Some(name) = first_page {
+                if let Some(name) = left_page {
                     <page::Page
                         {db}
                         {notify}
                         {volume_id}
                         {name}
-                        node_ref={&self.node_right}
-                        bbox={self.window.right}
+                        cache={self.cache.clone()}
+                        queue={self.queue.clone()}
+                        highlight={self.highlight.clone()}
+                        node_ref={&self.node_left}
+                        bbox={self.window.left}
                         mutable={self.mutable}
+                        revision={self.revision}
                         onload={&self.handle_image_load}
                         focus_reader={&self.focus}
+                        record_edit={&self.record_edit}
                     />
                 }
-                if let Some(name) = second_page {
+                if let Some(name) = right_page {
                     <page::Page
                         {db}
                         {notify}
                         {volume_id}
                         {name}
-                        node_ref={&self.node_left}
-                        bbox={self.window.left}
+                        cache={self.cache.clone()}
+                        queue={self.queue.clone()}
+                        highlight={self.highlight.clone()}
+                        node_ref={&self.node_right}
+                        bbox={self.window.right}
                         mutable={self.mutable}
+                        revision={self.revision}
                         onload={&self.handle_image_load}
                         focus_reader={&self.focus}
+                        record_edit={&self.record_edit}
                     />
                 }
 
-                {pagebar(
-                    self.window.right.rect.height as u32,
-                    ctx.link().callback(|_| Self::Message::NextPage),
-                )}
+                {pagebar(pagebar_height, right_bar)}
 
                 </div>
+                if self.panel == Some(EditPanel::Find) {
+                    <find::FindPanel
+                        {db}
+                        {notify}
+                        {volume_id}
+                        pages={volume.pages.clone()}
+                        queue={self.queue.clone()}
+                        apply={&self.apply_edit}
+                        go_to_block={&self.go_to_block}
+                        close={&self.close_panel}
+                    />
+                }
+                if self.panel == Some(EditPanel::Lint) {
+                    <linter::LintPanel
+                        {db}
+                        {notify}
+                        {volume_id}
+                        pages={volume.pages.clone()}
+                        {spread}
+                        queue={self.queue.clone()}
+                        apply={&self.apply_edit}
+                        go_to_block={&self.go_to_block}
+                        close={&self.close_panel}
+                    />
+                }
+                if self.panel == Some(EditPanel::Normalize) {
+                    <NormalizeModal
+                        {db}
+                        {notify}
+                        {volume_id}
+                        close_modal={&self.close_panel}
+                        latest={&self.latest_ocr}
+                        commit={&self.normalize}
+                    />
+                }
                 if self.show_help {{help(self.mutable)}}
+                if let Some(status) = &self.save_status {{save_indicator(status)}}
             </div>
             };
         }
@@ -349,11 +798,20 @@ fn pagebar(
     }
 }
 
+fn save_indicator(status: &queue::SaveStatus) -> Html {
+    let (class, text) = match status {
+        queue::SaveStatus::Saving => ("saving", "saving…"),
+        queue::SaveStatus::Saved => ("saved", "saved"),
+        queue::SaveStatus::Failed(_) => ("failed", "not saved"),
+    };
+    html! { <span id="SaveIndicator" {class}>{text}</span> }
+}
+
 fn help(editing: bool) -> Html {
     const HELP: &str =
-        "H - Toggle Help | Z - Next Page | X - Previous Page | E - Toggle Editing | S - Toggle Sidebar | Right Click - Toggle Magnifier";
+        "H - Toggle Help | Z - Next Page | X - Previous Page | M - Toggle Bookmark | C - Toggle Chapter | B - Toggle Page Break | E - Toggle Editing | S - Toggle Sidebar | Right Click - Toggle Magnifier";
     const EDITING: &str =
-        "\"-\" - Decrease Font | \"+\" - Increase Font | 0 - Autosize Box to Text |  \"\\\" - Toggle Text Opacity | BACKSPACE - Delete Textbox";
+        "\"-\" - Decrease Font | \"+\" - Increase Font | 0 - Autosize Box to Text |  \"\\\" - Toggle Text Opacity | BACKSPACE - Delete Textbox | F - Find & Replace | L - Check OCR | N - Normalize Text | CTRL+Z - Undo | CTRL+SHIFT+Z - Redo";
     html! {
         <span id="HelpBanner">
             {if editing {format!("{HELP} || {EDITING}")} else {HELP.to_owned()} }
@@ -361,7 +819,67 @@ fn help(editing: bool) -> Html {
     }
 }
 
+/// Keyboard events targeting text inputs or text that is being edited
+/// should be left to the browser, i.e. the native Ctrl+Z behavior.
+fn is_text_input(e: &KeyboardEvent) -> bool {
+    e.target()
+        .and_then(|target| target.dyn_into::<web_sys::HtmlElement>().ok())
+        .is_some_and(|elm| elm.is_content_editable() || elm.tag_name() == "INPUT")
+}
+
 impl Reader {
+    /// Applies an edit to the latest (i.e. possibly queued) OCR of its pages, and queues the
+    /// pages to be written. Blocks which have changed since the edit was made are skipped,
+    /// as are pages whose OCR cannot be read. Returns the applied changes and the first error.
+    async fn apply_edit(
+        db: Rc<Rexie>, volume_id: VolumeId, cache: cache::PageCache, queue: queue::OcrQueue, edit: Edit,
+    ) -> (Edit, Option<Notification>) {
+        let (mut applied, mut error) = (vec![], None);
+        for page in edit.pages() {
+            match Self::latest_ocr(&db, volume_id, &queue, &page).await {
+                Ok(mut ocr) => {
+                    let edits = edit.apply(&page, &mut ocr);
+                    if edits.is_empty() { continue; }
+                    applied.extend(edits);
+                    cache.set_ocr(volume_id, &page, &ocr);
+                    queue.push(volume_id, page, ocr);
+                }
+                Err(err) => {
+                    error.get_or_insert(Warning("failed to apply OCR edit", err.to_string()));
+                }
+            }
+        }
+        (Edit(applied), error)
+    }
+
+    /// Takes their version of a conflicting block, if the block has not changed since the merge.
+    async fn take_theirs(
+        db: Rc<Rexie>, volume_id: VolumeId, cache: cache::PageCache, queue: queue::OcrQueue,
+        page: AttrValue, conflict: BlockConflict,
+    ) -> ReaderMessage {
+        let mut ocr = match Self::latest_ocr(&db, volume_id, &queue, &page).await {
+            Ok(ocr) => ocr,
+            Err(err) => return ReaderMessage::Notify(Warning("failed to apply OCR edit", err.to_string())),
+        };
+        let mut rebased = conflict.clone();
+        let result = history::take_theirs(&mut rebased, &page, &mut ocr).ok_or(Box::new(rebased));
+        if result.is_ok() {
+            cache.set_ocr(volume_id, &page, &ocr);
+            queue.push(volume_id, page, ocr);
+        }
+        ReaderMessage::ConflictResolved(Box::new(conflict), result)
+    }
+
+    /// The latest OCR of the page, including OCR which is queued but not yet written.
+    async fn latest_ocr(
+        db: &Rc<Rexie>, volume_id: VolumeId, queue: &queue::OcrQueue, page: &AttrValue,
+    ) -> crate::Result<PageOcr> {
+        match queue.latest(volume_id, page) {
+            Some(ocr) => Ok(ocr),
+            None => get_ocr(db, &js_sys::Array::of2(&volume_id.into(), &page.as_str().into())).await,
+        }
+    }
+
     async fn commit_volume(db: Rc<Rexie>, volume: VolumeMetadata) -> ReaderMessage {
         // gloo_console::log!(format!("updating volume ({id} - {})", volume.title));
         match put_volume(&db, &volume).await {
@@ -373,12 +891,13 @@ impl Reader {
     }
 
     async fn fetch(db: Rc<Rexie>, volume_id: VolumeId) -> ReaderMessage {
-        match get_volume(&db, volume_id).await {
-            Ok(volume) => { ReaderMessage::Set(Box::new(volume)) }
-            Err(err) => ReaderMessage::Notify(
+        let volume = match get_volume(&db, volume_id).await {
+            Ok(volume) => volume,
+            Err(err) => return ReaderMessage::Notify(
                 Warning("failed to retrieve from IndexedDB", err.to_string())
             )
-        }
+        };
+        ReaderMessage::Set(Box::new(volume))
     }
 }
 
@@ -465,6 +984,234 @@ mod magnifier {
     }
 }
 
+mod cache {
+    use std::cell::RefCell;
+    use std::collections::{HashSet, VecDeque};
+    use std::rc::Rc;
+
+    use rexie::Rexie;
+    use yew::AttrValue;
+
+    use crate::models::{PageImage, PageOcr, VolumeId};
+    use crate::utils::db::get_page_and_ocr;
+
+    /// The number of pages which are kept, which must be more than the pages of
+    /// the current spread and the spreads on either side of it (i.e. six).
+    const CAPACITY: usize = 16;
+
+    type Key = (VolumeId, AttrValue);
+
+    /// PageCache keeps the images and OCR of recently displayed (and prefetched) pages,
+    /// so that turning the page does not wait on IndexedDB or image decoding.
+    /// The least recently used pages are evicted, revoking their object URLs once
+    /// they are no longer displayed.
+    #[derive(Clone, Default)]
+    pub struct PageCache(Rc<RefCell<Inner>>);
+
+    #[derive(Default)]
+    struct Inner {
+        /// Ordered from least to most recently used.
+        entries: VecDeque<(Key, CachedPage)>,
+        loading: HashSet<Key>,
+    }
+
+    struct CachedPage {
+        object_url: Rc<gloo_file::ObjectUrl>,
+        ocr: PageOcr,
+        /// Keeping a decoded copy of the image alive means the browser does not
+        /// need to decode it again when the page is displayed.
+        _image: Option<web_sys::HtmlImageElement>,
+    }
+
+    impl PartialEq for PageCache {
+        fn eq(&self, other: &Self) -> bool {
+            Rc::ptr_eq(&self.0, &other.0)
+        }
+    }
+
+    impl PageCache {
+        pub fn get(&self, id: VolumeId, name: &AttrValue) -> Option<(Rc<gloo_file::ObjectUrl>, PageOcr)> {
+            let mut inner = self.0.borrow_mut();
+            let position = inner.entries.iter().position(|(key, _)| key.0 == id && &key.1 == name)?;
+            let entry = inner.entries.remove(position)?;
+            let result = (entry.1.object_url.clone(), entry.1.ocr.clone());
+            inner.entries.push_back(entry);
+            Some(result)
+        }
+
+        pub fn insert(&self, id: VolumeId, name: AttrValue, image: PageImage, ocr: PageOcr) -> Rc<gloo_file::ObjectUrl> {
+            let object_url = Rc::new(gloo_file::ObjectUrl::from(image));
+            let _image = web_sys::HtmlImageElement::new().ok().inspect(|image| {
+                image.set_src(&object_url);
+                // Decoding failures are reported when the page is displayed instead.
+                let _ = image.decode();
+            });
+            let mut inner = self.0.borrow_mut();
+            inner.entries.retain(|(key, _)| key.0 != id || key.1 != name);
+            inner.entries.push_back(((id, name), CachedPage { object_url: object_url.clone(), ocr, _image }));
+            while inner.entries.len() > CAPACITY {
+                inner.entries.pop_front();
+            }
+            object_url
+        }
+
+        /// Replaces the cached OCR of a page, after it has been edited.
+        pub fn set_ocr(&self, id: VolumeId, name: &AttrValue, ocr: &PageOcr) {
+            let mut inner = self.0.borrow_mut();
+            if let Some((_, entry)) = inner.entries.iter_mut().find(|(key, _)| key.0 == id && &key.1 == name) {
+                entry.ocr = ocr.clone();
+            }
+        }
+
+        /// Loads the pages which are not cached yet, i.e. the spreads either side of the current spread.
+        pub async fn prefetch(self, db: Rc<Rexie>, id: VolumeId, names: Vec<AttrValue>) {
+            for name in names.into_iter() {
+                let key = (id, name.clone());
+                {
+                    let mut inner = self.0.borrow_mut();
+                    if inner.entries.iter().any(|(k, _)| k == &key) || !inner.loading.insert(key.clone()) {
+                        continue;
+                    }
+                }
+                let page_key = js_sys::Array::of2(&id.into(), &name.as_str().into());
+                let result = get_page_and_ocr(&db, &page_key.into()).await;
+                self.0.borrow_mut().loading.remove(&key);
+                // A page which fails to load is reported if it is displayed.
+                if let Ok((image, ocr)) = result {
+                    self.insert(id, name, image, ocr);
+                }
+            }
+        }
+    }
+}
+
+mod queue {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::rc::Rc;
+
+    use gloo_timers::callback::Timeout;
+    use rexie::Rexie;
+    use yew::{AttrValue, Callback};
+
+    use crate::models::{PageOcr, VolumeId};
+    use crate::utils::db::put_ocr;
+
+    /// How long to wait for further edits before writing the queued OCR.
+    const DELAY_MS: u32 = 500;
+    /// How long to wait before retrying the queue after a write fails.
+    const RETRY_MS: u32 = 5000;
+
+    type Key = (VolumeId, AttrValue);
+
+    #[derive(Clone, PartialEq)]
+    pub enum SaveStatus {
+        Saving,
+        Saved,
+        Failed(String),
+    }
+
+    /// OcrQueue batches the OCR writes of the reader. Queuing a page which is already
+    /// queued replaces its OCR, so that only the latest OCR of each page is written, and
+    /// the pages are written one at a time, so that writes cannot complete out of order.
+    /// The queue is written once edits stop for DELAY_MS, or when it is flushed.
+    #[derive(Clone)]
+    pub struct OcrQueue(Rc<RefCell<Inner>>);
+
+    struct Inner {
+        db: Rc<Rexie>,
+        /// Ordered by when each page was first queued.
+        pending: VecDeque<(Key, PageOcr)>,
+        /// The page which is currently being written.
+        writing: Option<(Key, PageOcr)>,
+        timeout: Option<Timeout>,
+        /// Whether the last write failed, i.e. the queue is waiting to be retried.
+        failed: bool,
+        report: Callback<SaveStatus>,
+    }
+
+    impl PartialEq for OcrQueue {
+        fn eq(&self, other: &Self) -> bool {
+            Rc::ptr_eq(&self.0, &other.0)
+        }
+    }
+
+    impl OcrQueue {
+        pub fn new(db: Rc<Rexie>, report: Callback<SaveStatus>) -> Self {
+            let inner = Inner { db, pending: VecDeque::new(), writing: None, timeout: None, failed: false, report };
+            Self(Rc::new(RefCell::new(inner)))
+        }
+
+        pub fn push(&self, id: VolumeId, name: AttrValue, ocr: PageOcr) {
+            let queue = self.clone();
+            let mut inner = self.0.borrow_mut();
+            let key = (id, name);
+            match inner.pending.iter_mut().find(|(k, _)| k == &key) {
+                Some((_, pending)) => *pending = ocr,
+                None => inner.pending.push_back((key, ocr)),
+            }
+            inner.timeout = Some(Timeout::new(DELAY_MS, move || queue.flush()));
+            // The failure remains displayed until the failed write succeeds.
+            if !inner.failed {
+                inner.report.emit(SaveStatus::Saving);
+            }
+        }
+
+        /// The OCR of the page which has been queued, but not written yet.
+        pub fn latest(&self, id: VolumeId, name: &AttrValue) -> Option<PageOcr> {
+            let inner = self.0.borrow();
+            let is_page = |(key, _): &&(Key, PageOcr)| key.0 == id && &key.1 == name;
+            inner.pending.iter().find(is_page).or(inner.writing.as_ref().filter(is_page))
+                .map(|(_, ocr)| ocr.clone())
+        }
+
+        pub fn is_idle(&self) -> bool {
+            let inner = self.0.borrow();
+            inner.pending.is_empty() && inner.writing.is_none()
+        }
+
+        /// Starts writing the queued pages immediately.
+        pub fn flush(&self) {
+            let mut inner = self.0.borrow_mut();
+            inner.timeout = None;
+            // A write in progress continues on to the rest of the queue.
+            if inner.writing.is_none() && !inner.pending.is_empty() {
+                wasm_bindgen_futures::spawn_local(self.clone().write());
+            }
+        }
+
+        /// Writes the queued pages in order. If a write fails, the page is queued again
+        /// (unless it has been edited since, i.e. a newer OCR is queued) and the rest of
+        /// the queue is retried after RETRY_MS, so that no edit is dropped.
+        async fn write(self) {
+            loop {
+                let (db, (key, ocr)) = {
+                    let mut inner = self.0.borrow_mut();
+                    let Some(next) = inner.pending.pop_front() else { break; };
+                    inner.writing = Some(next.clone());
+                    (inner.db.clone(), next)
+                };
+                let result = put_ocr(&db, key.0, &key.1, &ocr).await;
+                let mut inner = self.0.borrow_mut();
+                inner.writing = None;
+                if let Err(err) = result {
+                    if !inner.pending.iter().any(|(k, _)| k == &key) {
+                        inner.pending.push_front((key, ocr));
+                    }
+                    let queue = self.clone();
+                    inner.timeout = Some(Timeout::new(RETRY_MS, move || queue.flush()));
+                    inner.failed = true;
+                    inner.report.emit(SaveStatus::Failed(err.to_string()));
+                    return;
+                }
+            }
+            let mut inner = self.0.borrow_mut();
+            inner.failed = false;
+            inner.report.emit(SaveStatus::Saved);
+        }
+    }
+}
+
 mod page {
     use std::rc::Rc;
 
@@ -474,12 +1221,15 @@ mod page {
     use web_sys::{ClipboardEvent, MouseEvent};
     use yew::{html, AttrValue, Callback, Component, Context, Event, Html, NodeRef, Properties};
 
-    use crate::models::{OcrBlock, PageImage, PageOcr, VolumeId};
+    use crate::models::{OcrBlock, PageOcr, VolumeId};
     use crate::notify::Notification;
-    use crate::utils::db::{get_page_and_ocr, put_ocr};
+    use crate::utils::db::{get_ocr, get_page_and_ocr};
     use crate::utils::web::{focus, get_selection};
 
+    use super::cache::PageCache;
     use super::drag::Drag;
+    use super::queue::OcrQueue;
+    use super::history::{BlockEdit, Edit};
     use super::window::BoundingBox;
 
     #[derive(Properties, PartialEq)]
@@ -488,17 +1238,22 @@ mod page {
         pub notify: Callback<Notification>,
         pub volume_id: VolumeId,
         pub name: AttrValue,
+        pub cache: PageCache,
+        pub queue: OcrQueue,
+        pub highlight: Option<AttrValue>,
         pub node_ref: NodeRef,
         pub bbox: BoundingBox,
         pub mutable: bool,
+        pub revision: u64,
         pub onload: Callback<Event>,
         pub focus_reader: Callback<()>,
+        pub record_edit: Callback<Edit>,
     }
 
     pub enum PageMessage {
-        Set(PageImage, PageOcr),
+        Set(Rc<gloo_file::ObjectUrl>, PageOcr),
+        SetOcr(PageOcr),
         Notify(Notification),
-        Refresh,
         ReportBlur(NodeRef),
         DeleteBlock(AttrValue),
         UpdateBlock(OcrBlock),
@@ -508,7 +1263,9 @@ mod page {
     }
 
     pub struct Page {
-        _url_object: Option<gloo_file::ObjectUrl>,
+        /// Shared with the PageCache, so the URL is only revoked once it is
+        /// both evicted from the cache and no longer displayed.
+        _url_object: Option<Rc<gloo_file::ObjectUrl>>,
         drag: Option<Drag>,
         last_focus: Option<NodeRef>,
         ocr: PageOcr,
@@ -569,23 +1326,35 @@ mod page {
         }
 
         fn changed(&mut self, ctx: &Context<Self>, previous: &Self::Properties) -> bool {
-            let Props { db, volume_id, name, .. } = ctx.props();
+            let Props { db, volume_id, name, cache, queue, .. } = ctx.props();
             if *volume_id != previous.volume_id || name != &previous.name {
-                self._url_object = None;  // TODO: reconsider
+                // The previous page is displayed until the new page has been loaded.
+                if let Some((object_url, ocr)) = cache.get(*volume_id, name) {
+                    self.set(object_url, ocr);
+                    return true;
+                }
                 ctx.link().send_future(enclose!(
-                    (db, volume_id => id, name) Self::fetch(db, id, name)
+                    (db, volume_id => id, name, cache, queue) Self::fetch(db, id, name, cache, queue)
                 ));
                 return false;
             }
+            if ctx.props().revision != previous.revision {
+                ctx.link().send_future(enclose!(
+                    (db, volume_id => id, name, queue) Self::fetch_ocr(db, id, name, queue)
+                ));
+            }
             true
         }
 
         fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
             match msg {
-                PageMessage::Set(image, ocr) => {
-                    let object = gloo_file::ObjectUrl::from(image);
-                    self.url = AttrValue::from(object.to_string());
-                    self._url_object = Some(object);
+                PageMessage::Set(object_url, ocr) => {
+                    self.set(object_url, ocr);
+                    true
+                }
+                PageMessage::SetOcr(ocr) => {
+                    let Props { volume_id, name, cache, .. } = ctx.props();
+                    cache.set_ocr(*volume_id, name, &ocr);
                     self.ocr = ocr;
                     true
                 }
@@ -593,21 +1362,17 @@ mod page {
                     ctx.props().notify.emit(notification);
                     false
                 }
-                PageMessage::Refresh => true,
                 PageMessage::ReportBlur(node) => {
                     self.last_focus = Some(node);
                     false
                 }
                 PageMessage::DeleteBlock(uuid) => {
                     if let Some(index) = self.ocr.blocks.iter().position(|b| b.uuid == uuid) {
-                        self.ocr.blocks.remove(index);
+                        let block = self.ocr.blocks.remove(index);
+                        self.record(ctx, index, Some(block), None);
                     };
 
-                    let ocr = self.ocr.clone();
-                    let Props { db, volume_id, name, .. } = ctx.props();
-                    ctx.link().send_future(enclose!(
-                        (db, volume_id => id, name) Self::commit_ocr(db, id, name, ocr)
-                    ));
+                    self.commit(ctx);
                     if let Some(node) = &self.last_focus {
                         if !focus(node) {
                             ctx.props().focus_reader.emit(());
@@ -624,14 +1389,12 @@ mod page {
                         return true;
                     }
                     if let Some(index) = self.ocr.blocks.iter().position(|b| b.uuid == block.uuid) {
-                        self.ocr.blocks[index] = block;
+                        if self.ocr.blocks[index] == block { return true; }
+                        let previous = std::mem::replace(&mut self.ocr.blocks[index], block.clone());
+                        self.record(ctx, index, Some(previous), Some(block));
                     };
 
-                    let ocr = self.ocr.clone();
-                    let Props { db, volume_id, name, .. } = ctx.props();
-                    ctx.link().send_future(enclose!(
-                        (db, volume_id => id, name) Self::commit_ocr(db, id, name, ocr)
-                    ));
+                    self.commit(ctx);
                     true
                 }
                 PageMessage::BeginDrag(x, y) => {
@@ -650,14 +1413,11 @@ mod page {
                         // Prevent creating a new block from a click.
                         if !drag.dirty() { return true; }
 
-                        let Props { bbox, db, name, volume_id, .. } = ctx.props();
+                        let bbox = &ctx.props().bbox;
                         let block = create_block(&drag, bbox, self.scale(bbox));
-                        self.ocr.blocks.push(block);
-
-                        let ocr = self.ocr.clone();
-                        ctx.link().send_future(enclose!(
-                            (db, volume_id => id, name) Self::commit_ocr(db, id, name, ocr)
-                        ));
+                        self.ocr.blocks.push(block.clone());
+                        self.record(ctx, self.ocr.blocks.len() - 1, None, Some(block));
+                        self.commit(ctx);
                         true
                     } else { false }
                 }
@@ -666,9 +1426,13 @@ mod page {
 
         fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
             if first_render {
-                let Props { db, volume_id, name, .. } = ctx.props();
+                let Props { db, volume_id, name, cache, queue, .. } = ctx.props();
+                if let Some((object_url, ocr)) = cache.get(*volume_id, name) {
+                    ctx.link().send_message(PageMessage::Set(object_url, ocr));
+                    return;
+                }
                 ctx.link().send_future(enclose!(
-                    (db, volume_id => id, name) Self::fetch(db, id, name)
+                    (db, volume_id => id, name, cache, queue) Self::fetch(db, id, name, cache, queue)
                 ))
             }
         }
@@ -678,7 +1442,7 @@ mod page {
                 return Html::default();
             }
 
-            let Props { bbox, node_ref, onload, mutable, .. } = ctx.props();
+            let Props { bbox, node_ref, onload, mutable, highlight, .. } = ctx.props();
             let draggable = Some("false");
             let src = &self.url;
             let scale = self.scale(bbox);
@@ -712,6 +1476,7 @@ mod page {
                         html!{ <super::ocr::TextBlock
                             key={block.uuid.as_str()}
                             {mutable}
+                            highlighted={highlight.as_ref() == Some(&block.uuid)}
                             bbox={*bbox}
                             {scale}
                             block={block.clone()}
@@ -729,10 +1494,21 @@ mod page {
     }
 
     impl Page {
-        async fn fetch(db: Rc<Rexie>, id: VolumeId, name: AttrValue) -> PageMessage {
+        fn set(&mut self, object_url: Rc<gloo_file::ObjectUrl>, ocr: PageOcr) {
+            self.url = AttrValue::from(object_url.to_string());
+            self._url_object = Some(object_url);
+            self.ocr = ocr;
+        }
+
+        async fn fetch(
+            db: Rc<Rexie>, id: VolumeId, name: AttrValue, cache: PageCache, queue: OcrQueue,
+        ) -> PageMessage {
             let key = js_sys::Array::of2(&id.into(), &name.as_str().into());
             match get_page_and_ocr(&db, &key.into()).await {
-                Ok((image, ocr)) => PageMessage::Set(image, ocr),
+                Ok((image, ocr)) => {
+                    let ocr = queue.latest(id, &name).unwrap_or(ocr);
+                    PageMessage::Set(cache.insert(id, name, image, ocr.clone()), ocr)
+                }
                 Err(err) => PageMessage::Notify(
                     Notification::Warning(
                         "failed to retrieve Page and Ocr data from IndexedDB",
@@ -741,14 +1517,34 @@ mod page {
                 )
             }
         }
-        async fn commit_ocr(db: Rc<Rexie>, id: VolumeId, name: AttrValue, ocr: PageOcr) -> PageMessage {
+        async fn fetch_ocr(db: Rc<Rexie>, id: VolumeId, name: AttrValue, queue: OcrQueue) -> PageMessage {
+            if let Some(ocr) = queue.latest(id, &name) {
+                return PageMessage::SetOcr(ocr);
+            }
             let key = js_sys::Array::of2(&id.into(), &name.as_str().into());
-            if let Err(err) = put_ocr(&db, &ocr, &key).await {
-                return PageMessage::Notify(
-                    Notification::Warning("failed to save OCR", err.to_string())
-                );
-            };
-            PageMessage::Refresh
+            match get_ocr(&db, &key).await {
+                Ok(ocr) => PageMessage::SetOcr(ocr),
+                Err(err) => PageMessage::Notify(
+                    Notification::Warning("failed to retrieve Ocr data from IndexedDB", err.to_string())
+                )
+            }
+        }
+
+        /// Queues the OCR of this page to be written, and updates the cached copy.
+        fn commit(&self, ctx: &Context<Self>) {
+            let Props { volume_id, name, cache, queue, .. } = ctx.props();
+            cache.set_ocr(*volume_id, name, &self.ocr);
+            queue.push(*volume_id, name.clone(), self.ocr.clone());
+        }
+
+        /// Reports a change to one of the blocks of this page to the undo history.
+        fn record(
+            &self, ctx: &Context<Self>, index: usize,
+            before: Option<OcrBlock>, after: Option<OcrBlock>,
+        ) {
+            let Props { name, record_edit, .. } = ctx.props();
+            let page = name.clone();
+            record_edit.emit(Edit::from(BlockEdit { page, index, before, after }));
         }
 
         #[inline(always)]
@@ -778,7 +1574,7 @@ mod ocr {
     use enclose::enclose;
     use wasm_bindgen::JsCast;
     use web_sys::{Event, FocusEvent, KeyboardEvent, MouseEvent};
-    use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties};
+    use yew::{classes, html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties};
 
     use crate::models::OcrBlock;
     use crate::notify::Notification;
@@ -788,13 +1584,15 @@ mod ocr {
     use super::drag::Drag;
     use super::window::BoundingBox;
 
-    const DELETE_PROMPT: &str = "Are you sure you want to delete this?\nThere is no undo!";
+    const DELETE_PROMPT: &str = "Are you sure you want to delete this?\n(Ctrl+Z will undo this)";
 
     #[derive(Properties, PartialEq)]
     pub struct Props {
         pub bbox: BoundingBox,
         pub block: OcrBlock,
         pub mutable: bool,
+        /// Whether the block is focused and displayed, i.e. it is the result of a search.
+        pub highlighted: bool,
         pub scale: f64,
 
         pub notify: Callback<Notification>,
@@ -907,7 +1705,7 @@ mod ocr {
                 contenteditable: false,
                 drag: None,
                 node_ref: NodeRef::default(),
-                should_be_focused: false,
+                should_be_focused: ctx.props().highlighted,
                 transparent: false,
                 stamp: timestamp(),
                 begin_drag,
@@ -1019,7 +1817,7 @@ mod ocr {
                         ((bottom - bbox.rect.top) * scale).round() as u32,
                     );
                     let mut block = block.clone();
-                    block.box_ = box_;
+                    block.fit_box(box_);
                     ctx.props().commit_block.emit(block.to_owned());
                     true
                 }
@@ -1052,7 +1850,7 @@ mod ocr {
                     }
 
                     let mut block = block.clone();
-                    block.box_ = box_;
+                    block.set_box(box_);
                     commit_block.emit(block.to_owned());
                     self.transparent = true;
                     true
@@ -1088,7 +1886,7 @@ mod ocr {
                     }
                     if lines != block.lines || lines.is_empty() {
                         self.stamp = timestamp();  // Use this to force redraw.
-                        block.lines = lines;
+                        block.set_lines(lines);
                         ctx.props().commit_block.emit(block);
                     }
                     false
@@ -1101,6 +1899,13 @@ mod ocr {
             }
         }
 
+        fn changed(&mut self, ctx: &Context<Self>, previous: &Self::Properties) -> bool {
+            if ctx.props().highlighted && !previous.highlighted {
+                self.should_be_focused = true;
+            }
+            true
+        }
+
         fn rendered(&mut self, _ctx: &Context<Self>, _first_render: bool) {
             if self.should_be_focused {
                 // Focus on the first <p> tag. This minimizes the chance that the
@@ -1120,6 +1925,7 @@ mod ocr {
                 bbox,
                 block,
                 mutable,
+                highlighted,
                 scale,
                 commit_block,
                 oncopy,
@@ -1138,7 +1944,7 @@ mod ocr {
                     let box_ = (left as u32, top as u32, right as u32, bottom as u32);
                     if box_ != block.box_ {
                         let mut block = block.clone();
-                        block.box_ = box_;
+                        block.set_box(box_);
                         commit_block.emit(block.to_owned());
                     }
                     Self::Message::EndDrag
@@ -1168,7 +1974,7 @@ mod ocr {
                 <div
                   ref={&self.node_ref}
                   key={format!("{}-{}", block.uuid.as_str(), self.stamp)}
-                  class={"ocr-block"}
+                  class={classes!("ocr-block", highlighted.then_some("highlighted"))}
                   contenteditable={self.contenteditable.then_some("true")}
                   {style} tabindex={"0"}
                   {onblur} {oncopy} {ondblclick}
@@ -1177,6 +1983,15 @@ mod ocr {
                 >
                     {if block.lines.iter().all(|line| line.trim().is_empty()) {
                         html!{<p>{"placeholder"}</p>}
+                    } else if let Some(bounds) = block.line_bounds() {
+                        // Position each line where it sits on the page.
+                        block.lines.iter().zip(bounds).map(|(line, (left, top, ..))| {
+                            let style = format!(
+                                "position: absolute; top: {:.2}px; left: {:.2}px;",
+                                (top - block.top()) / scale, (left - block.left()) / scale,
+                            );
+                            html!{<p {style}>{line}</p>}
+                        }).collect::<Html>()
                     } else {
                         block.lines.iter().map(
                             |line| html!{<p>{line}</p>}
@@ -1269,12 +2084,860 @@ mod ocr {
     }
 }
 
-mod drag {
-    #[derive(Clone, Copy, Debug, PartialEq)]
-    pub struct Drag {
-        start_x: i32,
-        start_y: i32,
-        pos_x: i32,
+mod find {
+    use std::collections::{BTreeMap, BTreeSet};
+    use std::rc::Rc;
+
+    use enclose::enclose;
+    use rexie::Rexie;
+    use web_sys::MouseEvent;
+    use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties, SubmitEvent};
+
+    use crate::models::{PageOcr, VolumeId};
+    use crate::notify::{Notification, Notification::Warning};
+    use crate::utils::db::{count_search_entries, get_ocr, get_search_candidates};
+    use crate::utils::replace::{replace_block, FindOptions, Finder, Match};
+    use crate::utils::search::query_grams;
+    use crate::utils::web::{get_input_bool, get_input_value};
+
+    use super::history::{BlockEdit, Edit};
+    use super::queue::OcrQueue;
+
+    /// The OCR of pages, with their index and name.
+    pub type Pages = Vec<(usize, AttrValue, PageOcr)>;
+
+    #[derive(Properties, PartialEq)]
+    pub struct Props {
+        pub db: Rc<Rexie>,
+        pub notify: Callback<Notification>,
+        pub volume_id: VolumeId,
+        pub pages: Box<[(AttrValue, AttrValue)]>,
+        pub queue: OcrQueue,
+        /// Applies the replacements, which are undone/redone together.
+        pub apply: Callback<Edit>,
+        pub go_to_block: Callback<(usize, AttrValue)>,
+        pub close: Callback<MouseEvent>,
+    }
+
+    pub enum Message {
+        Find,
+        Found(FindOptions, Pages),
+        Toggle(usize),
+        ToggleAll,
+        Replace(bool),
+        Replaced(Edit, Pages),
+        Notify(Notification),
+    }
+
+    /// FindPanel lists every match of a pattern (literal text or a regular expression) within
+    /// the text of a volume, and replaces either the selected matches or all of them at once.
+    pub struct FindPanel {
+        options: FindOptions,
+        finder: Option<Finder>,
+        /// The OCR of every page, as of the last search.
+        ocr: Pages,
+        matches: Vec<Match>,
+        selected: Vec<bool>,
+        busy: bool,
+
+        pattern: NodeRef,
+        replacement: NodeRef,
+        regex: NodeRef,
+        match_case: NodeRef,
+        onsubmit: Callback<SubmitEvent>,
+    }
+
+    impl Component for FindPanel {
+        type Message = Message;
+        type Properties = Props;
+
+        fn create(ctx: &Context<Self>) -> Self {
+            let onsubmit = ctx.link().callback(|e: SubmitEvent| {
+                e.prevent_default();
+                Message::Find
+            });
+            Self {
+                options: FindOptions::default(),
+                finder: None,
+                ocr: vec![],
+                matches: vec![],
+                selected: vec![],
+                busy: false,
+                pattern: NodeRef::default(),
+                replacement: NodeRef::default(),
+                regex: NodeRef::default(),
+                match_case: NodeRef::default(),
+                onsubmit,
+            }
+        }
+
+        fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+            let Props { db, notify, volume_id, pages, queue, apply, .. } = ctx.props();
+            match msg {
+                Message::Find => {
+                    let options = FindOptions {
+                        pattern: get_input_value(&self.pattern).unwrap_or_default(),
+                        replacement: get_input_value(&self.replacement).unwrap_or_default(),
+                        regex: get_input_bool(&self.regex).unwrap_or_default(),
+                        match_case: get_input_bool(&self.match_case).unwrap_or_default(),
+                    };
+                    if options.pattern.is_empty() || self.busy { return false; }
+                    if let Err(err) = Finder::new(&options) {
+                        notify.emit(Warning("invalid find pattern", err.to_string()));
+                        return false;
+                    }
+                    self.busy = true;
+                    let pages: Vec<_> = pages.iter().map(|(name, _)| name.clone()).enumerate().collect();
+                    ctx.link().send_future(enclose!((db, volume_id, queue) async move {
+                        let pages = candidates(&db, volume_id, &queue, &options, pages).await;
+                        match load(&db, volume_id, &queue, pages).await {
+                            Ok(ocr) => Message::Found(options, ocr),
+                            Err(notification) => Message::Notify(notification),
+                        }
+                    }));
+                    true
+                }
+                Message::Found(options, ocr) => {
+                    self.busy = false;
+                    self.finder = Finder::new(&options).ok();
+                    self.options = options;
+                    self.ocr = ocr;
+                    self.refresh();
+                    true
+                }
+                Message::Toggle(index) => {
+                    if let Some(selected) = self.selected.get_mut(index) {
+                        *selected = !*selected;
+                    }
+                    true
+                }
+                Message::ToggleAll => {
+                    let select = !self.selected.iter().all(|selected| *selected);
+                    self.selected.iter_mut().for_each(|selected| *selected = select);
+                    true
+                }
+                Message::Replace(all) => {
+                    if self.busy { return false; }
+                    let matches: Vec<Match> = self.matches.iter().zip(self.selected.iter())
+                        .filter(|(_, selected)| all || **selected)
+                        .map(|(m, _)| m.clone())
+                        .collect();
+                    if matches.is_empty() { return false; }
+                    self.busy = true;
+                    ctx.link().send_future(enclose!((db, volume_id, queue) async move {
+                        match replace_matches(&db, volume_id, &queue, matches).await {
+                            Ok((edit, ocr)) => Message::Replaced(edit, ocr),
+                            Err(notification) => Message::Notify(notification),
+                        }
+                    }));
+                    true
+                }
+                Message::Replaced(edit, latest) => {
+                    self.busy = false;
+                    update_pages(&mut self.ocr, latest);
+                    if edit.0.is_empty() {
+                        notify.emit(Warning("nothing was replaced", "the text has changed since the search".to_owned()));
+                    } else {
+                        apply.emit(edit);
+                    }
+                    self.refresh();
+                    true
+                }
+                Message::Notify(notification) => {
+                    self.busy = false;
+                    notify.emit(notification);
+                    true
+                }
+            }
+        }
+
+        fn view(&self, ctx: &Context<Self>) -> Html {
+            let Props { go_to_block, close, .. } = ctx.props();
+            let FindOptions { pattern, replacement, regex, match_case } = &self.options;
+            let selected = self.selected.iter().filter(|selected| **selected).count();
+            let status = if self.busy {
+                "Searching...".to_owned()
+            } else if self.finder.is_some() {
+                format!("{} match(es), {selected} selected", self.matches.len())
+            } else { String::new() };
+            let toggle_all = ctx.link().callback(|_: MouseEvent| Message::ToggleAll);
+            let replace_selected = ctx.link().callback(|_: MouseEvent| Message::Replace(false));
+            let replace_all = ctx.link().callback(|_: MouseEvent| Message::Replace(true));
+
+            html! {
+                <div id="FindPanel" class="edit-panel">
+                    <div class="panel-header">
+                        <span>{"Find & Replace"}</span>
+                        <div class="close-symbol" onclick={close}>{crate::icons::close()}</div>
+                    </div>
+                    <form onsubmit={&self.onsubmit}>
+                        <input ref={&self.pattern} type="text" placeholder="Find" value={pattern.clone()}/>
+                        <input ref={&self.replacement} type="text" placeholder="Replace with" value={replacement.clone()}/>
+                        <label><input ref={&self.regex} type="checkbox" checked={*regex}/>{"Regex"}</label>
+                        <label><input ref={&self.match_case} type="checkbox" checked={*match_case}/>{"Match Case"}</label>
+                        <button type="submit">{"Find"}</button>
+                    </form>
+                    <p class="panel-status">{status}</p>
+                    if !self.matches.is_empty() {
+                        <div class="panel-actions">
+                            <button onclick={toggle_all}>{"Select All/None"}</button>
+                            <button onclick={replace_selected} disabled={selected == 0}>{"Replace Selected"}</button>
+                            <button onclick={replace_all}>{"Replace All"}</button>
+                        </div>
+                    }
+                    <div class="panel-list">
+                        {for self.matches.iter().zip(self.selected.iter()).enumerate().map(|(index, (m, checked))| {
+                            let onchange = ctx.link().callback(move |_| Message::Toggle(index));
+                            let target = (m.page, m.block.clone());
+                            let onclick = go_to_block.reform(move |_: MouseEvent| target.clone());
+                            html! {
+                                <div class="panel-item">
+                                    <input type="checkbox" checked={*checked} {onchange}/>
+                                    <span class="panel-item-text" {onclick}>
+                                        <span class="panel-item-page">{format!("p. {}", m.page + 1)}</span>
+                                        {&m.before}<del>{&m.text}</del><ins>{&m.replacement}</ins>{&m.after}
+                                    </span>
+                                </div>
+                            }
+                        })}
+                    </div>
+                </div>
+            }
+        }
+    }
+
+    impl FindPanel {
+        /// Finds the matches within the OCR of the last search, which are all selected.
+        fn refresh(&mut self) {
+            let Some(finder) = &self.finder else { return; };
+            self.matches = self.ocr.iter()
+                .flat_map(|(page, page_name, ocr)| finder.find_page(*page, page_name, ocr))
+                .collect();
+            self.selected = vec![true; self.matches.len()];
+        }
+    }
+
+    /// Replaces the matches within the latest OCR of their pages (as it may have been edited
+    /// since they were found), returning the edit and the OCR of the pages after the edit.
+    /// The edit is empty if none of the matches could be replaced.
+    pub async fn replace_matches(
+        db: &Rc<Rexie>, volume_id: VolumeId, queue: &OcrQueue, matches: Vec<Match>,
+    ) -> Result<(Edit, Pages), Notification> {
+        let mut pages: Vec<_> = matches.iter().map(|m| (m.page, m.page_name.clone())).collect();
+        pages.sort();
+        pages.dedup();
+        let mut latest = load(db, volume_id, queue, pages).await?;
+
+        let mut edits = vec![];
+        for (page, page_name, ocr) in latest.iter_mut() {
+            let mut blocks: BTreeMap<&AttrValue, Vec<&Match>> = BTreeMap::new();
+            for m in matches.iter().filter(|m| m.page == *page) {
+                blocks.entry(&m.block).or_default().push(m);
+            }
+            for (uuid, matches) in blocks {
+                let Some(index) = ocr.blocks.iter().position(|b| &b.uuid == uuid) else { continue; };
+                let Some(after) = replace_block(&ocr.blocks[index], &matches) else { continue; };
+                let before = std::mem::replace(&mut ocr.blocks[index], after.clone());
+                edits.push(BlockEdit { page: page_name.clone(), index, before: Some(before), after: Some(after) });
+            }
+        }
+        Ok((Edit(edits), latest))
+    }
+
+    /// Replaces the OCR of the pages with their latest OCR.
+    pub fn update_pages(pages: &mut Pages, latest: Pages) {
+        for (page, _, ocr) in latest {
+            if let Some((_, _, cached)) = pages.iter_mut().find(|(p, _, _)| *p == page) {
+                *cached = ocr;
+            }
+        }
+    }
+
+    /// The pages which may contain the pattern. For literal patterns of two or more
+    /// characters, these are the pages which contain its n-grams in the search index
+    /// (see utils::search), along with the pages whose edits are queued, as these have
+    /// not been indexed yet. Every page is searched if the volume is not fully indexed.
+    async fn candidates(
+        db: &Rc<Rexie>, volume_id: VolumeId, queue: &OcrQueue, options: &FindOptions, pages: Vec<(usize, AttrValue)>,
+    ) -> Vec<(usize, AttrValue)> {
+        let grams = if options.regex { vec![] } else { query_grams(&options.pattern) };
+        if grams.is_empty() { return pages; }
+        match count_search_entries(db, volume_id).await {
+            Ok(count) if count >= pages.len() => {}
+            _ => return pages,
+        }
+        let Ok(entries) = get_search_candidates(db, &grams).await else { return pages; };
+        let names: BTreeSet<AttrValue> = entries.into_iter()
+            .filter(|entry| entry.volume_id == volume_id)
+            .map(|entry| entry.page)
+            .collect();
+        pages.into_iter()
+            .filter(|(_, name)| names.contains(name) || queue.latest(volume_id, name).is_some())
+            .collect()
+    }
+
+    /// Reads the latest OCR of the pages, including OCR which is queued but not yet written.
+    pub async fn load(
+        db: &Rc<Rexie>, volume_id: VolumeId, queue: &OcrQueue, pages: Vec<(usize, AttrValue)>,
+    ) -> Result<Pages, Notification> {
+        let mut result = Vec::with_capacity(pages.len());
+        for (page, page_name) in pages {
+            let ocr = match queue.latest(volume_id, &page_name) {
+                Some(ocr) => ocr,
+                None => {
+                    let key = js_sys::Array::of2(&volume_id.into(), &page_name.as_str().into());
+                    get_ocr(db, &key).await.map_err(|err| {
+                        Warning("failed to retrieve Ocr data from IndexedDB", err.to_string())
+                    })?
+                }
+            };
+            result.push((page, page_name, ocr));
+        }
+        Ok(result)
+    }
+}
+
+mod linter {
+    use std::rc::Rc;
+
+    use enclose::enclose;
+    use rexie::Rexie;
+    use web_sys::{Event, HtmlInputElement, MouseEvent};
+    use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties, TargetCast};
+
+    use crate::models::{LintRule, VolumeId};
+    use crate::notify::{Notification, Notification::Warning};
+    use crate::utils::db::{get_settings, put_settings};
+    use crate::utils::lint::{lint_page, merge_rules, Issue};
+    use crate::utils::web::get_select_value;
+
+    use super::find::{load, replace_matches, update_pages, Pages};
+    use super::history::Edit;
+    use super::queue::OcrQueue;
+
+    #[derive(Properties, PartialEq)]
+    pub struct Props {
+        pub db: Rc<Rexie>,
+        pub notify: Callback<Notification>,
+        pub volume_id: VolumeId,
+        pub pages: Box<[(AttrValue, AttrValue)]>,
+        /// The names of the pages which are currently displayed.
+        pub spread: Vec<AttrValue>,
+        pub queue: OcrQueue,
+        /// Applies the fixes, which are undone/redone together.
+        pub apply: Callback<Edit>,
+        pub go_to_block: Callback<(usize, AttrValue)>,
+        pub close: Callback<MouseEvent>,
+    }
+
+    pub enum Message {
+        SetRules(Vec<LintRule>),
+        Lint,
+        Linted(Pages),
+        /// Fixes the issue with the given index, or every issue.
+        Fix(Option<usize>),
+        Fixed(Edit, Pages),
+        RulesToggle,
+        ToggleRule(usize),
+        ResetRules,
+        Import(gloo_file::File),
+        Imported(Vec<LintRule>),
+        Notify(Notification),
+    }
+
+    struct File {
+        _url_object: gloo_file::ObjectUrl,
+        url: AttrValue,
+    }
+
+    /// LintPanel lists the suspicious text (i.e. likely OCR errors) of the displayed pages or
+    /// of the entire volume, found by the rules of the settings, each with a one-click fix.
+    /// The rules can be enabled/disabled, and imported or exported as JSON.
+    pub struct LintPanel {
+        rules: Option<Vec<LintRule>>,
+        /// The exported rules, which is recreated whenever the rules change.
+        export: Option<File>,
+        /// The OCR of the pages which were linted.
+        ocr: Pages,
+        issues: Option<Vec<Issue>>,
+        busy: bool,
+        show_rules: bool,
+
+        scope: NodeRef,
+        onimport: Callback<Event>,
+    }
+
+    impl Component for LintPanel {
+        type Message = Message;
+        type Properties = Props;
+
+        fn create(ctx: &Context<Self>) -> Self {
+            let Props { db, .. } = ctx.props();
+            ctx.link().send_future(enclose!((db) fetch_rules(db)));
+            let onimport = ctx.link().batch_callback(|e: Event| {
+                let input: HtmlInputElement = e.target_unchecked_into();
+                let file = input.files().and_then(|files| files.item(0));
+                input.set_value("");
+                file.map(|file| Message::Import(file.into()))
+            });
+            Self {
+                rules: None,
+                export: None,
+                ocr: vec![],
+                issues: None,
+                busy: false,
+                show_rules: false,
+                scope: NodeRef::default(),
+                onimport,
+            }
+        }
+
+        fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+            let Props { db, notify, volume_id, pages, spread, queue, apply, .. } = ctx.props();
+            match msg {
+                Message::SetRules(rules) => {
+                    let json = serde_json::to_string_pretty(&rules).unwrap_or_default();
+                    let file = gloo_file::File::new("lint-rules.json", json.as_str());
+                    let _url_object = gloo_file::ObjectUrl::from(file);
+                    let url = AttrValue::from(_url_object.to_string());
+                    self.export = Some(File { _url_object, url });
+                    self.rules = Some(rules);
+                    self.refresh();
+                    true
+                }
+                Message::Lint => {
+                    if self.busy { return false; }
+                    self.busy = true;
+                    let volume = get_select_value(&self.scope).is_some_and(|scope| scope == "volume");
+                    let pages: Vec<_> = pages.iter().map(|(name, _)| name.clone()).enumerate()
+                        .filter(|(_, name)| volume || spread.contains(name))
+                        .collect();
+                    ctx.link().send_future(enclose!((db, volume_id, queue) async move {
+                        match load(&db, volume_id, &queue, pages).await {
+                            Ok(ocr) => Message::Linted(ocr),
+                            Err(notification) => Message::Notify(notification),
+                        }
+                    }));
+                    true
+                }
+                Message::Linted(ocr) => {
+                    self.busy = false;
+                    self.ocr = ocr;
+                    self.issues = Some(vec![]);
+                    self.refresh();
+                    true
+                }
+                Message::Fix(index) => {
+                    let Some(issues) = &self.issues else { return false; };
+                    if self.busy { return false; }
+                    let matches: Vec<_> = issues.iter().enumerate()
+                        .filter(|(i, _)| index.is_none_or(|index| index == *i))
+                        .map(|(_, issue)| issue.fix.clone())
+                        .collect();
+                    if matches.is_empty() { return false; }
+                    self.busy = true;
+                    ctx.link().send_future(enclose!((db, volume_id, queue) async move {
+                        match replace_matches(&db, volume_id, &queue, matches).await {
+                            Ok((edit, ocr)) => Message::Fixed(edit, ocr),
+                            Err(notification) => Message::Notify(notification),
+                        }
+                    }));
+                    true
+                }
+                Message::Fixed(edit, latest) => {
+                    self.busy = false;
+                    update_pages(&mut self.ocr, latest);
+                    if edit.0.is_empty() {
+                        notify.emit(Warning("nothing was fixed", "the text has changed since it was linted".to_owned()));
+                    } else {
+                        apply.emit(edit);
+                    }
+                    self.refresh();
+                    true
+                }
+                Message::RulesToggle => {
+                    self.show_rules = !self.show_rules;
+                    true
+                }
+                Message::ToggleRule(index) => {
+                    let Some(mut rules) = self.rules.clone() else { return false; };
+                    let Some(rule) = rules.get_mut(index) else { return false; };
+                    rule.enabled = !rule.enabled;
+                    ctx.link().send_future(enclose!((db) save_rules(db, rules)));
+                    false
+                }
+                Message::ResetRules => {
+                    if !gloo_dialogs::confirm("Replace the lint rules (including imported rules) with the default rules?") {
+                        return false;
+                    }
+                    ctx.link().send_future(enclose!((db) save_rules(db, LintRule::defaults())));
+                    false
+                }
+                Message::Import(file) => {
+                    ctx.link().send_future(import(file));
+                    false
+                }
+                Message::Imported(imported) => {
+                    let Some(mut rules) = self.rules.clone() else { return false; };
+                    merge_rules(&mut rules, imported);
+                    ctx.link().send_future(enclose!((db) save_rules(db, rules)));
+                    false
+                }
+                Message::Notify(notification) => {
+                    self.busy = false;
+                    notify.emit(notification);
+                    true
+                }
+            }
+        }
+
+        fn view(&self, ctx: &Context<Self>) -> Html {
+            let Props { go_to_block, close, .. } = ctx.props();
+            let status = match &self.issues {
+                _ if self.busy => "Checking...".to_owned(),
+                Some(issues) => format!("{} issue(s)", issues.len()),
+                None => String::new(),
+            };
+            let lint = ctx.link().callback(|_: MouseEvent| Message::Lint);
+            let fix_all = ctx.link().callback(|_: MouseEvent| Message::Fix(None));
+            let toggle_rules = ctx.link().callback(|_: MouseEvent| Message::RulesToggle);
+            let reset_rules = ctx.link().callback(|_: MouseEvent| Message::ResetRules);
+            let issues = self.issues.as_deref().unwrap_or_default();
+
+            html! {
+                <div id="LintPanel" class="edit-panel">
+                    <div class="panel-header">
+                        <span>{"Check OCR"}</span>
+                        <div class="close-symbol" onclick={close}>{crate::icons::close()}</div>
+                    </div>
+                    <div class="panel-actions">
+                        <select ref={&self.scope}>
+                            <option value="spread" selected=true>{"Displayed Pages"}</option>
+                            <option value="volume">{"Entire Volume"}</option>
+                        </select>
+                        <button onclick={lint} disabled={self.rules.is_none()}>{"Check"}</button>
+                        <button onclick={toggle_rules}>{if self.show_rules { "Hide Rules" } else { "Rules" }}</button>
+                    </div>
+                    if self.show_rules {
+                        <div class="panel-list lint-rules">
+                            {for self.rules.iter().flatten().enumerate().map(|(index, rule)| {
+                                let onchange = ctx.link().callback(move |_| Message::ToggleRule(index));
+                                html! {
+                                    <label class="panel-item">
+                                        <input type="checkbox" checked={rule.enabled} {onchange}/>
+                                        {&rule.name}
+                                    </label>
+                                }
+                            })}
+                        </div>
+                        <div class="panel-actions">
+                            <label for="lint-rules-upload" class="button">{"Import"}</label>
+                            <input id="lint-rules-upload" type="file" accept=".json" onchange={&self.onimport}/>
+                            if let Some(export) = &self.export {
+                                <a href={&export.url} download="lint-rules.json"><button>{"Export"}</button></a>
+                            }
+                            <button onclick={reset_rules}>{"Reset"}</button>
+                        </div>
+                    } else {
+                        <p class="panel-status">{status}</p>
+                        if !issues.is_empty() {
+                            <div class="panel-actions">
+                                <button onclick={fix_all}>{"Fix All"}</button>
+                            </div>
+                        }
+                        <div class="panel-list">
+                            {for issues.iter().enumerate().map(|(index, Issue { rule, fix })| {
+                                let onfix = ctx.link().callback(move |_: MouseEvent| Message::Fix(Some(index)));
+                                let target = (fix.page, fix.block.clone());
+                                let onclick = go_to_block.reform(move |_: MouseEvent| target.clone());
+                                html! {
+                                    <div class="panel-item">
+                                        <button onclick={onfix}>{"Fix"}</button>
+                                        <span class="panel-item-text" {onclick} title={rule}>
+                                            <span class="panel-item-page">{format!("p. {}", fix.page + 1)}</span>
+                                            {&fix.before}<del>{&fix.text}</del><ins>{&fix.replacement}</ins>{&fix.after}
+                                        </span>
+                                    </div>
+                                }
+                            })}
+                        </div>
+                    }
+                </div>
+            }
+        }
+    }
+
+    impl LintPanel {
+        /// Lints the OCR of the last check with the current rules.
+        fn refresh(&mut self) {
+            let (Some(rules), Some(issues)) = (&self.rules, &mut self.issues) else { return; };
+            *issues = self.ocr.iter()
+                .flat_map(|(page, page_name, ocr)| lint_page(rules, *page, page_name, ocr))
+                .collect();
+        }
+    }
+
+    async fn fetch_rules(db: Rc<Rexie>) -> Message {
+        match get_settings(&db).await {
+            Ok(settings) => Message::SetRules(settings.lint_rules),
+            Err(err) => Message::Notify(Warning("failed to retrieve settings from IndexedDB", err.to_string())),
+        }
+    }
+
+    /// Saves the rules to the (latest) settings.
+    async fn save_rules(db: Rc<Rexie>, rules: Vec<LintRule>) -> Message {
+        let result = match get_settings(&db).await {
+            Ok(mut settings) => {
+                settings.lint_rules = rules.clone();
+                put_settings(&db, &settings).await
+            }
+            Err(err) => Err(err),
+        };
+        match result {
+            Ok(()) => Message::SetRules(rules),
+            Err(err) => Message::Notify(Warning("failed to save settings to IndexedDB", err.to_string())),
+        }
+    }
+
+    async fn import(file: gloo_file::File) -> Message {
+        let rules = match gloo_file::futures::read_as_text(&file).await {
+            Ok(text) => serde_json::from_str::<Vec<LintRule>>(&text).map_err(crate::errors::AppError::from),
+            Err(err) => Err(err.into()),
+        };
+        match rules {
+            Ok(rules) => Message::Imported(rules),
+            Err(err) => Message::Notify(Warning("failed to import lint rules", err.to_string())),
+        }
+    }
+}
+
+mod history {
+    use yew::AttrValue;
+
+    use crate::models::{BlockConflict, OcrBlock, PageOcr};
+    use crate::utils::timestamp;
+
+    /// The maximum number of edits that can be undone.
+    const LIMIT: usize = 500;
+    /// Consecutive changes to the same textbox within this many milliseconds
+    /// are grouped into a single edit, i.e. holding down an arrow key.
+    /// Only the interactive edits of the page are grouped (see `History::record`).
+    const COALESCE_MS: u64 = 1000;
+
+    /// A change to a single textbox of a page.
+    /// A `before` of None means the block was created,
+    /// and an `after` of None means the block was deleted.
+    #[derive(Clone, PartialEq)]
+    pub struct BlockEdit {
+        pub page: AttrValue,
+        pub index: usize,
+        pub before: Option<OcrBlock>,
+        pub after: Option<OcrBlock>,
+    }
+
+    impl BlockEdit {
+        fn uuid(&self) -> Option<&AttrValue> {
+            self.after.as_ref().or(self.before.as_ref()).map(|block| &block.uuid)
+        }
+
+        fn invert(self) -> Self {
+            Self { page: self.page, index: self.index, before: self.after, after: self.before }
+        }
+
+        /// Applies the change if the block is as it was `before` the change,
+        /// returning whether it was applied.
+        fn apply(&self, ocr: &mut PageOcr) -> bool {
+            let Some(uuid) = self.uuid() else { return false; };
+            let position = ocr.blocks.iter().position(|b| &b.uuid == uuid);
+            match (&self.before, &self.after, position) {
+                (Some(before), Some(block), Some(index)) if &ocr.blocks[index] == before => {
+                    ocr.blocks[index] = block.clone();
+                }
+                (None, Some(block), None) => {
+                    let index = self.index.min(ocr.blocks.len());
+                    ocr.blocks.insert(index, block.clone());
+                }
+                (Some(before), None, Some(index)) if &ocr.blocks[index] == before => {
+                    ocr.blocks.remove(index);
+                }
+                _ => return false,
+            }
+            true
+        }
+    }
+
+    /// An Edit is a group of changes which are undone/redone together.
+    #[derive(Clone, Default, PartialEq)]
+    pub struct Edit(pub Vec<BlockEdit>);
+
+    impl From<BlockEdit> for Edit {
+        fn from(value: BlockEdit) -> Self { Self(vec![value]) }
+    }
+
+    impl Edit {
+        /// The (first) page affected by this edit.
+        pub fn page(&self) -> Option<&AttrValue> {
+            self.0.first().map(|edit| &edit.page)
+        }
+
+        /// All the pages affected by this edit, without duplicates.
+        pub fn pages(&self) -> Vec<AttrValue> {
+            let mut pages: Vec<AttrValue> = vec![];
+            for edit in self.0.iter() {
+                if !pages.contains(&edit.page) {
+                    pages.push(edit.page.clone());
+                }
+            }
+            pages
+        }
+
+        /// Apply the changes of this edit which belong to `page`, returning those which were
+        /// applied, i.e. skipping the changes of blocks which have been edited since.
+        pub fn apply(&self, page: &AttrValue, ocr: &mut PageOcr) -> Vec<BlockEdit> {
+            self.0.iter().filter(|edit| &edit.page == page && edit.apply(ocr)).cloned().collect()
+        }
+
+        fn invert(self) -> Self {
+            Self(self.0.into_iter().rev().map(BlockEdit::invert).collect())
+        }
+
+        /// Attempts to merge `other` into this edit. This is only possible if
+        /// both edits are modifications of the same (single) textbox.
+        fn merge(&mut self, other: &Self) -> bool {
+            match (self.0.as_mut_slice(), other.0.as_slice()) {
+                ([this], [that]) if this.before.is_some() && this.after.is_some()
+                    && that.before.is_some() && that.after.is_some()
+                    && this.page == that.page && this.uuid() == that.uuid() => {
+                    this.after.clone_from(&that.after);
+                    true
+                }
+                _ => false
+            }
+        }
+    }
+
+    /// Applies their version of the conflicting block of the page, returning the edit.
+    /// If the block has changed since the merge (i.e. it is no longer `ours`), nothing is
+    /// applied and the conflict is updated to the current block, so it can be resolved again.
+    pub fn take_theirs(conflict: &mut BlockConflict, page: &AttrValue, ocr: &mut PageOcr) -> Option<Edit> {
+        let edit = BlockEdit {
+            page: page.clone(),
+            index: conflict.index,
+            before: conflict.ours.clone(),
+            after: conflict.theirs.clone(),
+        };
+        if edit.apply(ocr) { return Some(edit.into()); }
+        let uuid = conflict.uuid().cloned();
+        conflict.ours = ocr.blocks.iter().find(|block| Some(&block.uuid) == uuid.as_ref()).cloned();
+        None
+    }
+
+    /// The undo/redo stacks of the edits made to a volume.
+    #[derive(Default)]
+    pub struct History {
+        undo: Vec<(Edit, u64)>,
+        redo: Vec<Edit>,
+    }
+
+    impl History {
+        /// Records an interactive edit of a textbox, i.e. moving or resizing it,
+        /// which is grouped with the previous edit of the textbox if it was recent.
+        pub fn record(&mut self, edit: Edit) {
+            if edit.0.is_empty() { return; }
+            let now = timestamp();
+            if let Some((last, stamp)) = self.undo.last_mut() {
+                if now.saturating_sub(*stamp) < COALESCE_MS && last.merge(&edit) {
+                    self.redo.clear();
+                    *stamp = now;
+                    return;
+                }
+            }
+            self.push(edit, now);
+        }
+
+        /// Records an edit which is never grouped with the edits before or after it,
+        /// i.e. a replacement, lint fix or normalization, which is undone on its own.
+        pub fn record_separately(&mut self, edit: Edit) {
+            if edit.0.is_empty() { return; }
+            self.push(edit, 0);
+        }
+
+        fn push(&mut self, edit: Edit, stamp: u64) {
+            self.redo.clear();
+            self.undo.push((edit, stamp));
+            if self.undo.len() > LIMIT {
+                self.undo.remove(0);
+            }
+        }
+
+        /// Returns the edit which reverts the most recent change.
+        pub fn undo(&mut self) -> Option<Edit> {
+            let (edit, _) = self.undo.pop()?;
+            self.redo.push(edit.clone());
+            Some(edit.invert())
+        }
+
+        /// Returns the edit which reapplies the most recently undone change.
+        pub fn redo(&mut self) -> Option<Edit> {
+            let edit = self.redo.pop()?;
+            // A redo should never be merged with the edit before it.
+            self.undo.push((edit.clone(), 0));
+            Some(edit)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn nudge(left: u32) -> Edit {
+            let block = |left| OcrBlock { uuid: "a".into(), box_: (left, 0, 10, 10), ..OcrBlock::default() };
+            Edit::from(BlockEdit { page: "1".into(), index: 0, before: Some(block(left)), after: Some(block(left + 1)) })
+        }
+
+        #[test]
+        fn groups_only_interactive_edits() {
+            let mut history = History::default();
+            history.record(nudge(0));
+            history.record(nudge(1));
+            history.record_separately(nudge(2));
+            history.record(nudge(3));
+            history.record(nudge(4));
+            let undone: Vec<u32> = std::iter::from_fn(|| history.undo())
+                .map(|edit| edit.0[0].after.as_ref().unwrap().box_.0)
+                .collect();
+            assert_eq!(undone, [3, 2, 0]);
+        }
+
+        #[test]
+        fn takes_theirs_only_if_the_block_is_unchanged() {
+            let block = |text: &str| OcrBlock { uuid: "a".into(), lines: vec![text.to_owned().into()], ..OcrBlock::default() };
+            let conflict = BlockConflict { page: 0, index: 0, ours: Some(block("ours")), theirs: Some(block("theirs")) };
+
+            let mut ocr = PageOcr { blocks: vec![block("ours")], ..PageOcr::default() };
+            let mut resolved = conflict.clone();
+            assert!(take_theirs(&mut resolved, &"1".into(), &mut ocr).is_some());
+            assert!(ocr.blocks == [block("theirs")]);
+
+            // The block was edited after the merge, so the conflict is updated instead.
+            let mut ocr = PageOcr { blocks: vec![block("edited")], ..PageOcr::default() };
+            let mut stale = conflict.clone();
+            assert!(take_theirs(&mut stale, &"1".into(), &mut ocr).is_none());
+            assert!(ocr.blocks == [block("edited")]);
+            assert!(stale.ours == Some(block("edited")) && stale.theirs == conflict.theirs);
+            // Which can then be resolved again.
+            assert!(take_theirs(&mut stale, &"1".into(), &mut ocr).is_some());
+            assert!(ocr.blocks == [block("theirs")]);
+        }
+    }
+}
+
+mod drag {
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub struct Drag {
+        start_x: i32,
+        start_y: i32,
+        pos_x: i32,
         pos_y: i32,
         dirty: bool,
     }
@@ -1334,15 +2997,21 @@ mod sidebar {
     use yew_router::prelude::Link;
 
     use crate::icons;
-    use crate::utils::web::{get_input_bool, get_input_f64, get_input_u16, get_input_u8};
+    use crate::models::{BlockConflict, OcrBlock, PageMarker, PageMode};
+    use crate::utils::web::{get_input_bool, get_input_f64, get_input_u16, get_input_u8, get_select_value};
     use crate::Route;
 
     #[derive(Properties, PartialEq)]
     pub struct Props {
         pub data: SidebarData,
         pub expanded: bool,
+        pub bookmarks: Vec<PageMarker>,
+        pub chapters: Vec<PageMarker>,
+        pub conflicts: Vec<BlockConflict>,
         pub commit: Callback<SidebarData>,
+        pub go_to_page: Callback<usize>,
         pub onblur: Callback<()>,
+        pub resolve_conflict: Callback<(usize, bool)>,
         pub toggle_sidebar: Callback<MouseEvent>,
     }
 
@@ -1355,6 +3024,8 @@ mod sidebar {
         pub magnifier_width: u16,
         pub magnifier_radius: u8,
         pub magnification: u16,
+        pub page_mode: PageMode,
+        pub right_to_left: bool,
 
         pub show_help: bool,
         pub show_magnifier: bool,
@@ -1372,6 +3043,8 @@ mod sidebar {
         magnifier_width_ref: NodeRef,
         magnifier_radius_ref: NodeRef,
         magnification_ref: NodeRef,
+        page_mode_ref: NodeRef,
+        right_to_left_ref: NodeRef,
         show_help_ref: NodeRef,
         show_magnifier_ref: NodeRef,
     }
@@ -1398,6 +3071,8 @@ mod sidebar {
                 magnifier_width_ref: NodeRef::default(),
                 magnifier_radius_ref: NodeRef::default(),
                 magnification_ref: NodeRef::default(),
+                page_mode_ref: NodeRef::default(),
+                right_to_left_ref: NodeRef::default(),
                 show_help_ref: NodeRef::default(),
                 show_magnifier_ref: NodeRef::default(),
             }
@@ -1411,6 +3086,14 @@ mod sidebar {
                         .unwrap_or(data.first_page_is_cover);
                     let hide_sidebar = get_input_bool(&self.hide_toggle_ref)
                         .unwrap_or(data.hide_sidebar);
+                    let right_to_left = get_input_bool(&self.right_to_left_ref)
+                        .unwrap_or(data.right_to_left);
+                    let page_mode = match get_select_value(&self.page_mode_ref).as_deref() {
+                        Some("double") => PageMode::Double,
+                        Some("single") => PageMode::Single,
+                        Some("auto") => PageMode::Auto,
+                        _ => data.page_mode,
+                    };
                     let show_help = get_input_bool(&self.show_help_ref)
                         .unwrap_or(data.show_help);
                     let show_magnifier = get_input_bool(&self.show_magnifier_ref)
@@ -1433,6 +3116,8 @@ mod sidebar {
                         magnifier_width,
                         magnifier_radius,
                         magnification,
+                        page_mode,
+                        right_to_left,
                         show_help,
                         show_magnifier,
                     };
@@ -1445,7 +3130,9 @@ mod sidebar {
         }
 
         fn view(&self, ctx: &Context<Self>) -> Html {
-            let Props { data, expanded, toggle_sidebar, .. } = ctx.props();
+            let Props {
+                data, expanded, bookmarks, chapters, conflicts, go_to_page, resolve_conflict, toggle_sidebar, ..
+            } = ctx.props();
             let onblur = &self.onblur;
             if !(*expanded || data.hide_sidebar) {
                 return html! {
@@ -1482,6 +3169,29 @@ mod sidebar {
                         />
                     </div>
 
+                    <div class="sidebar-input-container">
+                        <label for="page-mode">{"Page Mode"}</label>
+                        <select
+                            ref={&self.page_mode_ref}
+                            id="page-mode"
+                            onchange={&self.onchange}
+                        >
+                            <option value="double" selected={data.page_mode == PageMode::Double}>{"Two Pages"}</option>
+                            <option value="single" selected={data.page_mode == PageMode::Single}>{"Single Page"}</option>
+                            <option value="auto" selected={data.page_mode == PageMode::Auto}>{"Auto"}</option>
+                        </select>
+                    </div>
+
+                    <div class="sidebar-input-container">
+                        <label for="right-to-left">{"Right-To-Left"}</label>
+                        <input
+                            ref={&self.right_to_left_ref}
+                            id="right-to-left" type="checkbox"
+                            checked={data.right_to_left}
+                            onchange={&self.onchange}
+                        />
+                    </div>
+
                     <div class="sidebar-input-container">
                         <label for="line-height">{"Line-Height"}</label>
                         <input
@@ -1493,6 +3203,17 @@ mod sidebar {
                         />
                     </div>
 
+                    if !conflicts.is_empty() {
+                        <h3 class="sidebar-header">{format!("Merge Conflicts ({})", conflicts.len())}</h3>
+                        {merge_conflicts(conflicts, go_to_page, resolve_conflict)}
+                    }
+
+                    <h3 class="sidebar-header">{"Chapters"}</h3>
+                    {markers(chapters, go_to_page, "Press \"C\" to mark a chapter.")}
+
+                    <h3 class="sidebar-header">{"Bookmarks"}</h3>
+                    {markers(bookmarks, go_to_page, "Press \"M\" to bookmark a page.")}
+
                     <h3 class="sidebar-header">{"Magnifier Settings"}</h3>
                     <div class="sidebar-input-container">
                         <label for="show-magnifier">{"Show Magnifier"}</label>
@@ -1567,6 +3288,44 @@ mod sidebar {
             }
         }
     }
+
+    /// Renders the textboxes which were edited in both copies of a merged volume,
+    /// where the user can keep either version.
+    fn merge_conflicts(
+        conflicts: &[BlockConflict], go_to_page: &Callback<usize>, resolve: &Callback<(usize, bool)>,
+    ) -> Html {
+        let text = |block: &Option<OcrBlock>| match block {
+            Some(block) => block.lines.iter().map(|line| line.as_str()).collect::<String>(),
+            None => "(deleted)".to_owned(),
+        };
+        conflicts.iter().enumerate().map(|(index, conflict)| {
+            let page = conflict.page;
+            let onclick = go_to_page.reform(move |_: MouseEvent| page);
+            let keep = resolve.reform(move |_: MouseEvent| (index, false));
+            let take = resolve.reform(move |_: MouseEvent| (index, true));
+            html! {
+                <div class="sidebar-conflict">
+                    <button class="sidebar-marker" {onclick}>{format!("Page {}", page + 1)}</button>
+                    <p class="sidebar-note">{"Mine: "}{text(&conflict.ours)}</p>
+                    <p class="sidebar-note">{"Theirs: "}{text(&conflict.theirs)}</p>
+                    <button onclick={keep}>{"Keep Mine"}</button>
+                    <button onclick={take}>{"Take Theirs"}</button>
+                </div>
+            }
+        }).collect()
+    }
+
+    /// Renders a list of page markers, which move the reader to their page when clicked.
+    fn markers(markers: &[PageMarker], go_to_page: &Callback<usize>, empty: &'static str) -> Html {
+        if markers.is_empty() {
+            return html! { <p class="sidebar-note">{empty}</p> };
+        }
+        markers.iter().map(|marker| {
+            let page = marker.page;
+            let onclick = go_to_page.reform(move |_: MouseEvent| page);
+            html! { <button class="sidebar-marker" {onclick}>{marker.display()}</button> }
+        }).collect()
+    }
 }
 
 mod window {
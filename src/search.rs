@@ -0,0 +1,309 @@
+use std::ops::Range;
+use std::rc::Rc;
+
+use enclose::enclose;
+use rexie::Rexie;
+use serde::{Deserialize, Serialize};
+use web_sys::MouseEvent;
+use yew::{html, AttrValue, Callback, Component, Context, Html, NodeRef, Properties, SubmitEvent};
+use yew_router::components::Link;
+use yew_router::history::Location;
+use yew_router::scope_ext::{LocationHandle, RouterScopeExt};
+use yew_router::Routable;
+
+use crate::cover::Cover;
+use crate::models::VolumeMetadata;
+use crate::notify::{Notification, Notification::Warning};
+use crate::reader::ReaderQuery;
+use crate::utils::db::{get_all_volumes, rebuild_search_index};
+use crate::utils::natural_cmp;
+use crate::utils::search::{search, BlockMatch, PageResult, SearchResults, SearchScope};
+use crate::utils::web::{get_input_value, get_select_value};
+use crate::Route;
+
+#[derive(Properties, PartialEq)]
+pub struct Props {
+    pub db: Rc<Rexie>,
+    pub notify: Callback<Notification>,
+}
+
+/// The query string of the search page, e.g. /search?q=<text>&scope=series:<name>,
+/// so that searches can be bookmarked and revisited with the browser history.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub scope: String,
+}
+
+pub enum Message {
+    SetVolumes(Vec<VolumeMetadata>),
+    Navigate(SearchQuery),
+    Submit,
+    Rebuild,
+    Rebuilt,
+    /// The results of the search with the given generation.
+    Results(u64, SearchResults),
+    Notify(Notification),
+}
+
+/// Search displays the blocks of text across the library which contain the query,
+/// grouped by page. Each result opens the reader at its page with the block highlighted.
+pub struct Search {
+    query: SearchQuery,
+    volumes: Vec<VolumeMetadata>,
+    results: Option<SearchResults>,
+    searching: bool,
+    rebuilding: bool,
+    /// Incremented with each search, so that the results of an outdated search are discarded.
+    generation: u64,
+    input: NodeRef,
+    select: NodeRef,
+    onsubmit: Callback<SubmitEvent>,
+    rebuild: Callback<MouseEvent>,
+    _location_handle: Option<LocationHandle>,
+}
+
+impl Component for Search {
+    type Message = Message;
+    type Properties = Props;
+
+    fn create(ctx: &Context<Self>) -> Self {
+        let query = ctx.link().location().map(|location| parse_query(&location)).unwrap_or_default();
+        let onsubmit = ctx.link().callback(|e: SubmitEvent| {
+            e.prevent_default();
+            Message::Submit
+        });
+        let rebuild = ctx.link().callback(|_| Message::Rebuild);
+        let _location_handle = ctx.link().add_location_listener(
+            ctx.link().batch_callback(|location: Location| {
+                (location.path() == Route::Search.to_path()).then(|| Message::Navigate(parse_query(&location)))
+            })
+        );
+        Self {
+            query,
+            volumes: vec![],
+            results: None,
+            searching: false,
+            rebuilding: false,
+            generation: 0,
+            input: NodeRef::default(),
+            select: NodeRef::default(),
+            onsubmit,
+            rebuild,
+            _location_handle,
+        }
+    }
+
+    fn update(&mut self, ctx: &Context<Self>, msg: Self::Message) -> bool {
+        match msg {
+            Message::SetVolumes(mut volumes) => {
+                volumes.sort_by(|a, b| {
+                    natural_cmp(&a.series, &b.series).then_with(|| natural_cmp(&a.title, &b.title))
+                });
+                self.volumes = volumes;
+                true
+            }
+            Message::Navigate(query) => {
+                if query == self.query { return false; }
+                self.query = query;
+                self.search(ctx);
+                true
+            }
+            Message::Submit => {
+                let query = SearchQuery {
+                    q: get_input_value(&self.input).unwrap_or_default(),
+                    scope: get_select_value(&self.select).unwrap_or_default(),
+                };
+                if query == self.query {
+                    // Searching again picks up any changes to the library.
+                    self.search(ctx);
+                    return true;
+                }
+                // The search starts once the location listener reports the new query.
+                if let Some(navigator) = ctx.link().navigator() {
+                    let _ = navigator.push_with_query(&Route::Search, &query);
+                }
+                false
+            }
+            Message::Rebuild => {
+                if self.rebuilding { return false; }
+                self.rebuilding = true;
+                let Props { db, .. } = ctx.props();
+                ctx.link().send_future(enclose!((db) rebuild(db)));
+                true
+            }
+            Message::Rebuilt => {
+                self.rebuilding = false;
+                self.search(ctx);
+                true
+            }
+            Message::Results(generation, results) => {
+                if generation != self.generation { return false; }
+                self.searching = false;
+                self.results = Some(results);
+                true
+            }
+            Message::Notify(notification) => {
+                self.searching = false;
+                self.rebuilding = false;
+                ctx.props().notify.emit(notification);
+                true
+            }
+        }
+    }
+
+    fn rendered(&mut self, ctx: &Context<Self>, first_render: bool) {
+        if first_render {
+            let Props { db, .. } = ctx.props();
+            ctx.link().send_future(enclose!((db) fetch(db)));
+            self.search(ctx);
+        }
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let Props { db, notify } = ctx.props();
+        let mut series: Vec<_> = self.volumes.iter()
+            .map(|volume| &volume.series)
+            .filter(|series| !series.is_empty())
+            .collect();
+        series.dedup();
+        let scope = &self.query.scope;
+        let option = |value: String, text: &AttrValue| {
+            let selected = &value == scope;
+            html! { <option {value} {selected}>{text}</option> }
+        };
+
+        let status = match &self.results {
+            _ if self.searching => html! { <p>{"Searching..."}</p> },
+            None => Html::default(),
+            Some(results) if results.pages.is_empty() => html! { <p>{"No results"}</p> },
+            Some(results) if results.truncated => html! {
+                <p>{format!("Showing the first {} pages, narrow the search to see more", results.pages.len())}</p>
+            },
+            Some(results) => html! { <p>{format!("{} page(s)", results.pages.len())}</p> },
+        };
+        let results: Html = self.results.iter()
+            .flat_map(|results| results.pages.iter())
+            .map(|result| render_result(db, notify, result))
+            .collect();
+
+        html! {<>
+            <div id="HomeNavBar">
+                <div class="nav-buttons">
+                    <Link<Route> to={Route::Home}>{"Library"}</Link<Route>>
+                </div>
+                <div class="title">{"Search"}</div>
+                <div class="nav-buttons nav-buttons-right">
+                    <div class="rebuild" onclick={&self.rebuild}>
+                        {if self.rebuilding { "Rebuilding Index..." } else { "Rebuild Index" }}
+                    </div>
+                </div>
+            </div>
+            <div id="SearchContainer">
+                <form id="SearchForm" onsubmit={&self.onsubmit}>
+                    <input
+                      ref={&self.input} type="search" placeholder="Search the text of your volumes"
+                      value={self.query.q.clone()}
+                    />
+                    <select ref={&self.select}>
+                        {option(SearchScope::Library.to_string(), &"Entire Library".into())}
+                        <optgroup label="Series">
+                            {for series.into_iter().map(|series| {
+                                option(SearchScope::Series(series.clone()).to_string(), series)
+                            })}
+                        </optgroup>
+                        <optgroup label="Volumes">
+                            {for self.volumes.iter().map(|volume| {
+                                option(SearchScope::Volume(volume.id).to_string(), &volume.title)
+                            })}
+                        </optgroup>
+                    </select>
+                    <button type="submit">{"Search"}</button>
+                </form>
+                <div id="SearchStatus">{status}</div>
+                <div id="SearchResults">{results}</div>
+            </div>
+        </>}
+    }
+}
+
+impl Search {
+    fn search(&mut self, ctx: &Context<Self>) {
+        self.generation += 1;
+        if self.query.q.trim().is_empty() {
+            self.searching = false;
+            self.results = None;
+            return;
+        }
+        self.searching = true;
+        let Props { db, .. } = ctx.props();
+        let (generation, query, scope) =
+            (self.generation, self.query.q.clone(), SearchScope::parse(&self.query.scope));
+        ctx.link().send_future(enclose!((db) run(db, generation, query, scope)));
+    }
+}
+
+fn parse_query(location: &Location) -> SearchQuery {
+    location.query::<SearchQuery>().unwrap_or_default()
+}
+
+fn render_result(db: &Rc<Rexie>, notify: &Callback<Notification>, result: &PageResult) -> Html {
+    let PageResult { volume_id, volume_title, page, page_name, blocks } = result;
+    let (volume_id, page) = (*volume_id, *page);
+    let to = Route::Reader { volume_id };
+    let query = |block: &BlockMatch| {
+        Some(ReaderQuery { page: Some(page), block: Some(block.uuid.clone()) })
+    };
+    let alt = format!("{volume_title} page {}", page + 1);
+    html! {
+        <div class="search-result" key={format!("{volume_id}-{page}")}>
+            <Link<Route, ReaderQuery> to={to.clone()} query={blocks.first().and_then(query)}>
+                <Cover {db} {notify} {volume_id} page={page_name} {alt} small=true/>
+            </Link<Route, ReaderQuery>>
+            <div class="search-result-blocks">
+                <p class="search-result-title">{format!("{volume_title} — p. {}", page + 1)}</p>
+                {for blocks.iter().map(|block| html! {
+                    <Link<Route, ReaderQuery> to={to.clone()} query={query(block)}>
+                        <p class="search-result-text">{highlight(&block.text, &block.matches)}</p>
+                    </Link<Route, ReaderQuery>>
+                })}
+            </div>
+        </div>
+    }
+}
+
+/// Wraps the matched ranges of the text within <mark> tags.
+fn highlight(text: &str, matches: &[Range<usize>]) -> Html {
+    let mut end = 0;
+    let mut html = vec![];
+    for range in matches {
+        html.push(html! { {&text[end..range.start]} });
+        html.push(html! { <mark>{&text[range.clone()]}</mark> });
+        end = range.end;
+    }
+    html.push(html! { {&text[end..]} });
+    html.into_iter().collect()
+}
+
+async fn fetch(db: Rc<Rexie>) -> Message {
+    match get_all_volumes(db).await {
+        Ok(volumes) => Message::SetVolumes(volumes),
+        Err(err) => Message::Notify(Warning("failed to retrieve all volumes from IndexedDB", err.to_string())),
+    }
+}
+
+async fn rebuild(db: Rc<Rexie>) -> Message {
+    match rebuild_search_index(&db).await {
+        Ok(()) => Message::Rebuilt,
+        Err(err) => Message::Notify(Warning("failed to rebuild the search index", err.to_string())),
+    }
+}
+
+async fn run(db: Rc<Rexie>, generation: u64, query: String, scope: SearchScope) -> Message {
+    match search(&db, &query, &scope).await {
+        Ok(results) => Message::Results(generation, results),
+        Err(err) => Message::Notify(Warning("failed to search the library", err.to_string())),
+    }
+}
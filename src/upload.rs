@@ -5,13 +5,15 @@ use web_sys::{DragEvent, Event, FileList, HtmlInputElement, MouseEvent};
 use yew::{html, AttrValue, Callback, Component, Context, Html, Properties, TargetCast};
 use yew_router::components::Link;
 
+use crate::errors::{AppError, InvalidMokuroFileError};
 use crate::models::VolumeMetadata;
 use crate::notify::{Notification, Notification::Warning};
 use crate::utils::web::{ask_to_persist_storage, is_web_storage_persisted};
-use crate::utils::zip::extract_ziparchive;
+use crate::utils::mokuro::MOKURO_EXTENSION;
+use crate::utils::db::get_volumes_by_uuid;
+use crate::utils::zip::{extract_ziparchive, prepare_ziparchive, ImportMode, PreparedImport};
 use crate::Route;
 
-#[allow(dead_code)]
 pub struct ExtractionError {
     error: crate::errors::AppError,
     filename: String,
@@ -27,7 +29,9 @@ pub struct Props {
 pub enum Message {
     Prompt,
     Process(Vec<gloo_file::File>),
-    Set(Vec<Result<Preview, ExtractionError>>),
+    Set(Vec<Upload>),
+    Resolve(usize, Resolution),
+    Resolved(usize, Upload),
     StoragePersisted(bool),
     Notify(Notification),
 }
@@ -44,11 +48,35 @@ pub struct Preview {
     volume: VolumeMetadata,
 }
 
+/// The outcome of uploading a single file.
+pub enum Upload {
+    Complete(Box<Preview>),
+    Failed(ExtractionError),
+    /// The library already contains the volume, so the user must decide how to import it.
+    Duplicate(Box<Duplicate>),
+    /// A duplicate volume is being imported, after the user has decided how.
+    Importing,
+}
+
+pub struct Duplicate {
+    prepared: PreparedImport,
+    existing: VolumeMetadata,
+    filename: String,
+}
+
+#[derive(Clone, Copy)]
+pub enum Resolution {
+    Replace,
+    KeepBoth,
+    Merge,
+    MergeEdits,
+}
+
 /// UploadModal creates a modal overlay where users can upload zip archives.
 /// It tries to check if the user has enabled persisted storage for the site,
 ///   and if not prompts the user to enable it.
 pub struct UploadModal {
-    previews: Vec<Result<Preview, ExtractionError>>,
+    uploads: Vec<Upload>,
     persisted: Option<bool>,
     state: State,
     cancel_click: Callback<MouseEvent>,
@@ -80,7 +108,7 @@ impl Component for UploadModal {
             }
         });
         Self {
-            previews: vec![],
+            uploads: vec![],
             persisted: None,
             state: State::Default,
             cancel_click,
@@ -103,11 +131,34 @@ impl Component for UploadModal {
                 ctx.link().send_future(enclose!((db, files) process(db, files)));
                 true
             }
-            Message::Set(previews) => {
-                self.previews = previews;
+            Message::Set(uploads) => {
+                self.uploads = uploads;
                 self.state = State::Complete;
                 true
             }
+            Message::Resolve(index, resolution) => {
+                let Some(upload) = self.uploads.get_mut(index) else { return false; };
+                let Upload::Duplicate(duplicate) = std::mem::replace(upload, Upload::Importing) else {
+                    return false;
+                };
+                let Duplicate { prepared, existing, filename } = *duplicate;
+                let mode = match resolution {
+                    Resolution::Replace => ImportMode::Replace(existing),
+                    Resolution::KeepBoth => ImportMode::New,
+                    Resolution::Merge => ImportMode::Merge(existing),
+                    Resolution::MergeEdits => ImportMode::MergeEdits(existing),
+                };
+                ctx.link().send_future(enclose!((db) async move {
+                    Message::Resolved(index, import(&db, prepared, mode, filename).await)
+                }));
+                true
+            }
+            Message::Resolved(index, upload) => {
+                if let Some(slot) = self.uploads.get_mut(index) {
+                    *slot = upload;
+                }
+                true
+            }
             Message::StoragePersisted(persisted) => {
                 self.persisted = Some(persisted);
                 true
@@ -134,9 +185,9 @@ impl Component for UploadModal {
             State::Default => html! {},
             State::Processing => html! { <p>{"Processing..."}</p> },
             State::Complete => {
-                let previews: Vec<Html> = self.previews.iter().map(|maybe| {
-                    match maybe {
-                        Ok(p) => {
+                let previews: Vec<Html> = self.uploads.iter().enumerate().map(|(index, upload)| {
+                    match upload {
+                        Upload::Complete(p) => {
                             let volume_id = p.volume.id;
                             html! {
                                 <Link<Route> to={Route::Reader {volume_id}}>
@@ -147,13 +198,45 @@ impl Component for UploadModal {
                                 </Link<Route>>
                             }
                         }
-                        Err(err) => html! {
+                        Upload::Failed(err) => html! {
                             <div class="preview-item">
                                 <p>{"ERROR"}</p>
                                 <p>{"failed to load file"}</p>
                                 <p>{&err.filename}</p>
+                                if let Some(entry) = err.error.entry() {
+                                    <p class="preview-entry">{format!("at {entry}")}</p>
+                                }
                             </div>
+                        },
+                        Upload::Duplicate(duplicate) => {
+                            let resolve = |resolution| {
+                                ctx.link().callback(move |_: MouseEvent| Message::Resolve(index, resolution))
+                            };
+                            html! {
+                                <div class="preview-item">
+                                    <p>{"ALREADY IN LIBRARY"}</p>
+                                    <p>{&duplicate.existing.title}</p>
+                                    <p>{&duplicate.filename}</p>
+                                    <button onclick={resolve(Resolution::Replace)}
+                                        title="Delete the existing volume">{"Replace"}</button>
+                                    <button onclick={resolve(Resolution::KeepBoth)}
+                                        title="Add the upload alongside the existing volume">{"Keep Both"}</button>
+                                    <button onclick={resolve(Resolution::Merge)}
+                                        title="Take the new pages, but keep your reading progress and settings">
+                                        {"Merge"}
+                                    </button>
+                                    <button onclick={resolve(Resolution::MergeEdits)}
+                                        title="Merge the textbox edits of the upload into the existing volume">
+                                        {"Merge Edits"}
+                                    </button>
+                                </div>
+                            }
                         }
+                        Upload::Importing => html! {
+                            <div class="preview-item">
+                                <p>{"Importing..."}</p>
+                            </div>
+                        },
                     }
                 }).collect();
                 html! {<>
@@ -177,11 +260,14 @@ impl Component for UploadModal {
                     <div class="close-symbol" onclick={close_modal}>{crate::icons::close()}</div>
                     <p class="modal-title">{ "Upload Your Mokuro Manga Files" }</p>
                     <p class="modal-note">
-                        {"Only files generated from "}
+                        {"Files generated from "}
                         <a href={"https://github.com/bbonenfant/mokuro"} target="_blank">
                             {"this Mokuro fork"}
                         </a>
-                        {" are supported (*.mbz.zip)"}
+                        {" (*.mbz.zip) and upstream mokuro are supported. "}
+                        {"Upload a .mokuro file together with a .cbz/.zip of its images, "}
+                        {"or a .zip containing the .mokuro file (or _ocr directory) and images. "}
+                        {"Plain .cbz/.zip archives of images are imported without any text boxes."}
                     </p>
                     <label for="file-upload">
                         <div id="drop-container" {ondrop} {ondragover} {ondragenter}>
@@ -190,7 +276,7 @@ impl Component for UploadModal {
                             <p>{"click to browse"}</p>
                         </div>
                     </label>
-                    <input id="file-upload" type="file" accept="application/zip" multiple={true} {onchange}/>
+                    <input id="file-upload" type="file" accept=".zip,.cbz,.mokuro" multiple={true} {onchange}/>
                     {gallery}
                 </div>
             </div>
@@ -210,17 +296,67 @@ async fn persist_storage() -> Message {
 }
 
 async fn process(db: Rc<Rexie>, files: Vec<gloo_file::File>) -> Message {
-    let mut previews = Vec::with_capacity(files.len());
-    for file in files.into_iter() {
+    let mut uploads = Vec::with_capacity(files.len());
+    for (file, mokuro_file) in pair_mokuro_files(files).into_iter() {
         let filename = file.name();
-        previews.push(
-            extract_ziparchive(&db, file).await.map(|(volume, cover)| {
-                let url = AttrValue::from(cover.to_string());
-                Preview { _object_url: cover, url, volume }
-            }).map_err(|error| ExtractionError { error, filename })
-        )
+        let prepared = if filename.ends_with(MOKURO_EXTENSION) {
+            // a .mokuro file was uploaded without an archive of its images.
+            let stem = filename.trim_end_matches(MOKURO_EXTENSION);
+            let missing = InvalidMokuroFileError::MissingFile(format!("{stem}.cbz"));
+            Err(AppError::InvalidMokuroFile(missing))
+        } else {
+            prepare_ziparchive(file, mokuro_file).await
+        };
+        let prepared = match prepared {
+            Ok(prepared) => prepared,
+            Err(error) => {
+                uploads.push(Upload::Failed(ExtractionError { error, filename }));
+                continue;
+            }
+        };
+        let upload = match get_volumes_by_uuid(&db, &prepared.volume().volume_uuid).await {
+            Ok(existing) if !existing.is_empty() => {
+                let existing = existing.into_iter().next().unwrap_or_default();
+                Upload::Duplicate(Box::new(Duplicate { prepared, existing, filename }))
+            }
+            Ok(_) => import(&db, prepared, ImportMode::New, filename).await,
+            Err(error) => Upload::Failed(ExtractionError { error, filename }),
+        };
+        uploads.push(upload);
+    }
+    Message::Set(uploads)
+}
+
+async fn import(db: &Rc<Rexie>, prepared: PreparedImport, mode: ImportMode, filename: String) -> Upload {
+    match extract_ziparchive(db, prepared, mode).await {
+        Ok((volume, cover)) => {
+            let url = AttrValue::from(cover.to_string());
+            Upload::Complete(Box::new(Preview { _object_url: cover, url, volume }))
+        }
+        Err(error) => Upload::Failed(ExtractionError { error, filename }),
+    }
+}
+
+/// Pairs each uploaded .mokuro file with the archive (.cbz or .zip) of the same name,
+/// which is expected to contain the images of the volume.
+fn pair_mokuro_files(files: Vec<gloo_file::File>) -> Vec<(gloo_file::File, Option<gloo_file::File>)> {
+    let (mokuro_files, mut archives): (Vec<_>, Vec<_>) = files.into_iter()
+        .partition(|file| file.name().ends_with(MOKURO_EXTENSION));
+    let mut paired = Vec::with_capacity(archives.len() + mokuro_files.len());
+    for mokuro_file in mokuro_files.into_iter() {
+        let name = mokuro_file.name();
+        let stem = name.trim_end_matches(MOKURO_EXTENSION);
+        let position = archives.iter().position(|archive| {
+            let name = archive.name();
+            [".cbz", ".zip"].iter().any(|ext| name.strip_suffix(ext) == Some(stem))
+        });
+        match position {
+            Some(index) => paired.push((archives.remove(index), Some(mokuro_file))),
+            None => paired.push((mokuro_file, None)),
+        }
     }
-    Message::Set(previews)
+    paired.extend(archives.into_iter().map(|archive| (archive, None)));
+    paired
 }
 
 fn upload_files(files: FileList) -> Vec<gloo_file::File> {
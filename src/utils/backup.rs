@@ -0,0 +1,195 @@
+//! Backup and restore of the entire library.
+//!
+//! A backup is a zip archive containing a manifest of the global settings, and every
+//! volume within its own directory. Each directory is laid out the same as an exported
+//! volume (mokuro-metadata.json, the page images and the _ocr directory), alongside the
+//! OCR data of the pages as it was when the volume was imported (the _snapshots directory).
+use std::cell::Cell;
+use std::rc::Rc;
+
+use gloo_console as console;
+use gloo_file::futures::read_as_bytes as gloo_file_read;
+use rexie::Rexie;
+use serde::{Deserialize, Serialize};
+use yew::Callback;
+
+use crate::errors::{AppError, InvalidMokuroFileError};
+use crate::models::{PageOcr, Settings, VolumeMetadata};
+use crate::utils::blob_zip::{BlobZipArchive, BlobZipWriter};
+use crate::utils::db::{
+    delete_volume, get_all_volumes, get_page_and_ocr, get_settings, get_volume_snapshots,
+    put_settings, put_volume_snapshots,
+};
+use crate::utils::iso_timestamp;
+use crate::utils::zip::{
+    entry_error, finish_import, read_json, write_importing_volume, PendingVolume, METADATA_FILE,
+};
+
+const MANIFEST_FILE: &str = "mokuro-library.json";
+const SNAPSHOT_DIRECTORY: &str = "_snapshots";
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Manifest {
+    version: u32,
+    created_at: String,
+    settings: Settings,
+    /// The directory of each volume within the archive.
+    volumes: Vec<String>,
+}
+
+/// A backup which has been read and validated, but not yet restored.
+pub struct PreparedRestore {
+    archive: BlobZipArchive,
+    created_at: String,
+    settings: Settings,
+    volumes: Vec<PreparedVolume>,
+}
+
+/// A volume of a backup, alongside the directory it was read from and the
+/// snapshot of each page (in the same order as `volume.pages`).
+struct PreparedVolume {
+    directory: String,
+    pending: PendingVolume,
+    snapshots: Vec<Option<PageOcr>>,
+}
+
+impl PreparedRestore {
+    pub fn created_at(&self) -> &str {
+        &self.created_at
+    }
+
+    pub fn volume_count(&self) -> usize {
+        self.volumes.len()
+    }
+}
+
+/// construct a zip archive of every volume in the mokuro IndexedDB and the global settings.
+/// As with `create_ziparchive`, the archive is assembled page by page as a Blob.
+/// `progress` is called with (volumes written, total volumes) after each volume, and
+/// the backup stops (returning None) once `cancelled` is set.
+pub async fn create_library_archive(
+    db: Rc<Rexie>, progress: Callback<(usize, usize)>, cancelled: Rc<Cell<bool>>,
+) -> crate::Result<Option<gloo_file::File>> {
+    let settings = get_settings(&db).await?;
+    let mut volumes = get_all_volumes(db.clone()).await?;
+    volumes.sort_by_key(|volume| volume.id);
+
+    let created_at = iso_timestamp();
+    let directories = (1..=volumes.len()).map(|n| format!("volumes/{n:04}")).collect();
+    let manifest = Manifest { version: VERSION, created_at: created_at.clone(), settings, volumes: directories };
+
+    let mut archive = BlobZipWriter::new();
+    archive.add_file(MANIFEST_FILE, &serde_json::to_vec(&manifest)?)?;
+
+    let total = volumes.len();
+    for (index, (volume, directory)) in volumes.iter().zip(manifest.volumes.iter()).enumerate() {
+        let metadata = {
+            let mut volume = volume.clone();
+            volume.id = 0;
+            serde_json::to_vec(&volume)?
+        };
+        archive.add_file(&format!("{directory}/{METADATA_FILE}"), &metadata)?;
+
+        let id = volume.id.into();
+        let snapshots = get_volume_snapshots(&db, volume).await?;
+        for ((page_name, ocr_name), snapshot) in volume.pages.iter().zip(snapshots) {
+            if cancelled.get() { return Ok(None); }
+            let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+            let (image, ocr) = get_page_and_ocr(&db, &key.into()).await?;
+
+            let image_data = gloo_file_read(image.as_ref()).await?;
+            archive.add_file(&format!("{directory}/{page_name}"), &image_data)?;
+            archive.add_file(&format!("{directory}/{ocr_name}"), &serde_json::to_vec(&ocr)?)?;
+            if let Some(snapshot) = snapshot {
+                let name = format!("{directory}/{SNAPSHOT_DIRECTORY}/{ocr_name}");
+                archive.add_file(&name, &serde_json::to_vec(&snapshot)?)?;
+            }
+        }
+        if !cancelled.get() { progress.emit((index + 1, total)); }
+    }
+
+    let date = created_at.split('T').next().unwrap_or_default();
+    Ok(Some(archive.finish(&format!("mokuro-library-{date}.zip"))?))
+}
+
+/// read and validate a library backup, without writing anything to IndexedDB.
+/// The metadata and OCR data of every volume is read, the page images are read during the restore.
+pub async fn prepare_library_archive(file_obj: gloo_file::File) -> crate::Result<PreparedRestore> {
+    let archive = BlobZipArchive::new(file_obj.into()).await?;
+    if !archive.contains(MANIFEST_FILE) {
+        let missing = InvalidMokuroFileError::MissingFile(MANIFEST_FILE.to_string());
+        return Err(AppError::InvalidMokuroFile(missing));
+    }
+    let manifest: Manifest = read_json(&archive, MANIFEST_FILE).await?;
+    if manifest.version > VERSION {
+        let error = InvalidMokuroFileError::UnsupportedVersion(manifest.version);
+        return Err(entry_error(MANIFEST_FILE)(AppError::InvalidMokuroFile(error)));
+    }
+
+    let mut volumes = Vec::with_capacity(manifest.volumes.len());
+    for directory in manifest.volumes.into_iter() {
+        let volume: VolumeMetadata = read_json(&archive, &format!("{directory}/{METADATA_FILE}")).await?;
+        let missing = volume.pages.iter()
+            .flat_map(|(page_name, ocr_name)| [page_name, ocr_name])
+            .map(|name| format!("{directory}/{name}"))
+            .find(|entry| !archive.contains(entry));
+        if let Some(entry) = missing {
+            return Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(entry)));
+        }
+
+        let mut pages = Vec::with_capacity(volume.pages.len());
+        let mut snapshots = Vec::with_capacity(volume.pages.len());
+        for (page_name, ocr_name) in volume.pages.iter() {
+            let ocr = read_json(&archive, &format!("{directory}/{ocr_name}")).await?;
+            pages.push((format!("{directory}/{page_name}"), ocr));
+
+            let snapshot = format!("{directory}/{SNAPSHOT_DIRECTORY}/{ocr_name}");
+            let snapshot = if archive.contains(&snapshot) { Some(read_json(&archive, &snapshot).await?) } else { None };
+            snapshots.push(snapshot);
+        }
+        volumes.push(PreparedVolume { directory, pending: PendingVolume { volume, pages }, snapshots });
+    }
+    Ok(PreparedRestore { archive, created_at: manifest.created_at, settings: manifest.settings, volumes })
+}
+
+/// replace the entire library in the mokuro IndexedDB with the contents of the backup.
+/// The restored volumes are hidden as importing until every one has been written, and
+/// only then is the existing library deleted. A failure part way through deletes the
+/// restored volumes instead, leaving the existing library as it was.
+/// `progress` is called with (volumes restored, total volumes) after each volume.
+pub async fn restore_library_archive(
+    db: &Rc<Rexie>, prepared: PreparedRestore, progress: Callback<(usize, usize)>,
+) -> crate::Result<usize> {
+    let PreparedRestore { archive, settings, volumes, .. } = prepared;
+    let existing = get_all_volumes(db.clone()).await?;
+
+    let total = volumes.len();
+    let mut restored = Vec::with_capacity(total);
+    for (index, PreparedVolume { directory, pending, snapshots }) in volumes.into_iter().enumerate() {
+        let result = async {
+            let (volume, cover_image) = write_importing_volume(db, &archive, pending).await?;
+            let result = put_volume_snapshots(db, &volume, &snapshots).await.map_err(entry_error(&directory));
+            restored.push((volume, cover_image));
+            result
+        }.await;
+        if let Err(error) = result {
+            for (volume, _) in restored {
+                if let Err(rollback_error) = delete_volume(db, volume.id).await {
+                    console::warn!(format!("failed to roll back restore: {}", rollback_error));
+                }
+            }
+            return Err(error);
+        }
+        progress.emit((index + 1, total));
+    }
+
+    put_settings(db, &settings).await?;
+    for (mut volume, cover_image) in restored {
+        finish_import(db, &mut volume, &cover_image).await?;
+    }
+    for volume in existing {
+        delete_volume(db, volume.id).await?;
+    }
+    Ok(total)
+}
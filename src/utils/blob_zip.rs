@@ -0,0 +1,393 @@
+//! A minimal zip archive reader and writer which work with Blobs rather than wasm memory.
+//! The reader only holds the central directory in memory; each entry is read by slicing
+//! the Blob (i.e. an uploaded File) when requested. Supports stored and deflated entries,
+//! as well as zip64 archives.
+//! The writer copies each entry into a JS buffer as it is added, and the archive is
+//! assembled from these buffers as a Blob. Entries are always stored (uncompressed),
+//! and zip64 records are only written once the archive exceeds the limits of zip.
+use std::io::Read;
+
+use flate2::read::DeflateDecoder;
+use gloo_file::futures::read_as_bytes as gloo_file_read;
+use crate::errors::{AppError, InvalidMokuroFileError, ZipError};
+
+const EOCD_SIGNATURE: u32 = 0x06054b50;
+const EOCD_SIZE: u64 = 22;
+const ZIP64_EOCD_SIGNATURE: u32 = 0x06064b50;
+const ZIP64_LOCATOR_SIGNATURE: u32 = 0x07064b50;
+const ZIP64_LOCATOR_SIZE: u64 = 20;
+const ZIP64_EXTRA_FIELD: u16 = 0x0001;
+const CENTRAL_HEADER_SIGNATURE: u32 = 0x02014b50;
+const CENTRAL_HEADER_SIZE: usize = 46;
+const LOCAL_HEADER_SIGNATURE: u32 = 0x04034b50;
+const LOCAL_HEADER_SIZE: u64 = 30;
+const MAX_COMMENT_SIZE: u64 = u16::MAX as u64;
+
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// The most that deflate can expand its input (the longest match of 258 bytes in 2 bits).
+const MAX_DEFLATE_RATIO: u64 = 1032;
+/// The most which is allocated up front to decompress an entry; larger entries grow as they are read.
+const MAX_INITIAL_CAPACITY: u64 = 16 << 20;
+
+const VERSION: u16 = 20;
+const ZIP64_VERSION: u16 = 45;
+const UTF8_FLAG: u16 = 1 << 11;
+const DOS_DATE: u16 = (1 << 5) | 1;  // 1980-01-01
+const DIRECTORY_ATTRIBUTE: u32 = 0x10;
+
+struct ZipEntry {
+    name: String,
+    method: u16,
+    crc32: u32,
+    compressed_size: u64,
+    size: u64,
+    header_offset: u64,
+}
+
+pub struct BlobZipArchive {
+    blob: gloo_file::Blob,
+    entries: Vec<ZipEntry>,
+}
+
+impl BlobZipArchive {
+    /// Reads the central directory of the zip archive.
+    pub async fn new(blob: gloo_file::Blob) -> crate::Result<Self> {
+        let (offset, size) = find_central_directory(&blob).await?;
+        let directory = read_slice(&blob, offset, size).await?;
+
+        let mut entries = vec![];
+        let mut cursor = 0;
+        while cursor + CENTRAL_HEADER_SIZE <= directory.len() {
+            let header = &directory[cursor..];
+            if u32_at(header, 0) != CENTRAL_HEADER_SIGNATURE { break; }
+            let name_len = u16_at(header, 28) as usize;
+            let extra_len = u16_at(header, 30) as usize;
+            let comment_len = u16_at(header, 32) as usize;
+            let end = CENTRAL_HEADER_SIZE + name_len + extra_len;
+            if header.len() < end {
+                return Err(ZipError::InvalidArchive("Truncated central directory").into());
+            }
+
+            let name = decode_name(&header[CENTRAL_HEADER_SIZE..][..name_len], u16_at(header, 8));
+            let mut entry = ZipEntry {
+                name,
+                method: u16_at(header, 10),
+                crc32: u32_at(header, 16),
+                compressed_size: u32_at(header, 20) as u64,
+                size: u32_at(header, 24) as u64,
+                header_offset: u32_at(header, 42) as u64,
+            };
+            let extra = &header[CENTRAL_HEADER_SIZE + name_len..end];
+            read_zip64_extra_field(&mut entry, extra);
+            if u16_at(header, 8) & 1 != 0 {
+                return Err(ZipError::UnsupportedArchive("Password required to decrypt file").into());
+            }
+            if !entry.name.ends_with('/') {
+                entries.push(entry);
+            }
+            cursor += end + comment_len;
+        }
+        Ok(Self { blob, entries })
+    }
+
+    /// The names of the files within the archive (excluding directories).
+    pub fn file_names(&self) -> impl Iterator<Item=&str> {
+        self.entries.iter().map(|entry| entry.name.as_str())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.iter().any(|entry| entry.name == name)
+    }
+
+    /// Reads and decompresses a single file from the archive.
+    pub async fn read(&self, name: &str) -> crate::Result<Vec<u8>> {
+        let Some(entry) = self.entries.iter().find(|entry| entry.name == name) else {
+            return Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(name.to_owned())));
+        };
+
+        let header = read_slice(&self.blob, entry.header_offset, LOCAL_HEADER_SIZE).await?;
+        if header.len() < LOCAL_HEADER_SIZE as usize || u32_at(&header, 0) != LOCAL_HEADER_SIGNATURE {
+            return Err(ZipError::InvalidArchive("Invalid local file header").into());
+        }
+        let data_offset = entry.header_offset + LOCAL_HEADER_SIZE
+            + u16_at(&header, 26) as u64 + u16_at(&header, 28) as u64;
+        let compressed = read_slice(&self.blob, data_offset, entry.compressed_size).await?;
+
+        let data = match entry.method {
+            STORED => compressed,
+            DEFLATED => inflate(&compressed, entry.size)?,
+            _ => return Err(ZipError::UnsupportedArchive("Compression method not supported").into()),
+        };
+        if data.len() as u64 != entry.size {
+            return Err(ZipError::InvalidArchive("Invalid file size").into());
+        }
+        if crc32fast::hash(&data) != entry.crc32 {
+            return Err(ZipError::InvalidArchive("Invalid checksum").into());
+        }
+        Ok(data)
+    }
+}
+
+pub struct BlobZipWriter {
+    parts: js_sys::Array,
+    directory: Vec<u8>,
+    entries: u64,
+    offset: u64,
+}
+
+impl BlobZipWriter {
+    pub fn new() -> Self {
+        Self { parts: js_sys::Array::new(), directory: vec![], entries: 0, offset: 0 }
+    }
+
+    pub fn add_directory(&mut self, name: &str) -> crate::Result<()> {
+        let name = if name.ends_with('/') { name.to_owned() } else { format!("{name}/") };
+        self.add_entry(&name, &[], DIRECTORY_ATTRIBUTE)
+    }
+
+    pub fn add_file(&mut self, name: &str, content: &[u8]) -> crate::Result<()> {
+        self.add_entry(name, content, 0)
+    }
+
+    fn add_entry(&mut self, name: &str, content: &[u8], attributes: u32) -> crate::Result<()> {
+        let size: u32 = content.len().try_into()
+            .map_err(|_| ZipError::UnsupportedArchive("Files over 4GB are not supported"))?;
+        let name_len: u16 = name.len().try_into()
+            .map_err(|_| ZipError::InvalidArchive("File name is too long"))?;
+        let crc32 = crc32fast::hash(content);
+        // Entries beyond the first 4GB of the archive record their offset in a zip64 extra field.
+        let zip64 = self.offset >= u32::MAX as u64;
+        let version = if zip64 { ZIP64_VERSION } else { VERSION };
+
+        // The fields shared by the local file header and the central directory header.
+        let mut fields = vec![];
+        put_u16(&mut fields, version);
+        put_u16(&mut fields, UTF8_FLAG);
+        put_u16(&mut fields, STORED);
+        put_u16(&mut fields, 0);
+        put_u16(&mut fields, DOS_DATE);
+        put_u32(&mut fields, crc32);
+        put_u32(&mut fields, size);
+        put_u32(&mut fields, size);
+        put_u16(&mut fields, name_len);
+
+        let mut header = Vec::with_capacity(LOCAL_HEADER_SIZE as usize + name.len());
+        put_u32(&mut header, LOCAL_HEADER_SIGNATURE);
+        header.extend_from_slice(&fields);
+        put_u16(&mut header, 0);  // extra field length
+        header.extend_from_slice(name.as_bytes());
+
+        put_u32(&mut self.directory, CENTRAL_HEADER_SIGNATURE);
+        put_u16(&mut self.directory, version);
+        self.directory.extend_from_slice(&fields);
+        put_u16(&mut self.directory, if zip64 { 12 } else { 0 });  // extra field length
+        put_u16(&mut self.directory, 0);  // comment length
+        put_u16(&mut self.directory, 0);  // disk number
+        put_u16(&mut self.directory, 0);  // internal attributes
+        put_u32(&mut self.directory, attributes);
+        put_u32(&mut self.directory, self.offset.min(u32::MAX as u64) as u32);
+        self.directory.extend_from_slice(name.as_bytes());
+        if zip64 {
+            put_u16(&mut self.directory, ZIP64_EXTRA_FIELD);
+            put_u16(&mut self.directory, 8);
+            put_u64(&mut self.directory, self.offset);
+        }
+
+        self.offset += header.len() as u64 + size as u64;
+        self.entries += 1;
+        self.parts.push(&js_sys::Uint8Array::from(&header[..]));
+        self.parts.push(&js_sys::Uint8Array::from(content));
+        Ok(())
+    }
+
+    /// Writes the central directory and assembles the archive into a File.
+    pub fn finish(mut self, name: &str) -> crate::Result<gloo_file::File> {
+        let directory_size = self.directory.len() as u64;
+        let mut end = Vec::with_capacity(EOCD_SIZE as usize);
+        let zip64 = self.entries >= u16::MAX as u64
+            || self.offset >= u32::MAX as u64
+            || directory_size >= u32::MAX as u64;
+        if zip64 {
+            // The zip64 end of central directory record, followed by its locator.
+            put_u32(&mut end, ZIP64_EOCD_SIGNATURE);
+            put_u64(&mut end, 44);  // size of the remaining record
+            put_u16(&mut end, ZIP64_VERSION);
+            put_u16(&mut end, ZIP64_VERSION);
+            put_u32(&mut end, 0);
+            put_u32(&mut end, 0);
+            put_u64(&mut end, self.entries);
+            put_u64(&mut end, self.entries);
+            put_u64(&mut end, directory_size);
+            put_u64(&mut end, self.offset);
+
+            put_u32(&mut end, ZIP64_LOCATOR_SIGNATURE);
+            put_u32(&mut end, 0);
+            put_u64(&mut end, self.offset + directory_size);
+            put_u32(&mut end, 1);
+        }
+        let entries = self.entries.min(u16::MAX as u64) as u16;
+        put_u32(&mut end, EOCD_SIGNATURE);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, 0);
+        put_u16(&mut end, entries);
+        put_u16(&mut end, entries);
+        put_u32(&mut end, if zip64 { u32::MAX } else { directory_size as u32 });
+        put_u32(&mut end, if zip64 { u32::MAX } else { self.offset as u32 });
+        put_u16(&mut end, 0);
+        self.directory.append(&mut end);
+        self.parts.push(&js_sys::Uint8Array::from(&self.directory[..]));
+
+        let blob = web_sys::Blob::new_with_u8_array_sequence(&self.parts)?;
+        Ok(gloo_file::File::new(name, gloo_file::Blob::from(blob)))
+    }
+}
+
+/// Locates the (offset, size) of the central directory from the end of central directory record.
+async fn find_central_directory(blob: &gloo_file::Blob) -> crate::Result<(u64, u64)> {
+    // The end of central directory record is followed by a variable length comment.
+    let size = blob.size();
+    let start = size.saturating_sub(EOCD_SIZE + MAX_COMMENT_SIZE);
+    let tail = read_slice(blob, start, size - start).await?;
+    let position = (0..tail.len().saturating_sub(EOCD_SIZE as usize - 1)).rev()
+        .find(|&i| u32_at(&tail, i) == EOCD_SIGNATURE)
+        .ok_or(ZipError::InvalidArchive("Could not find central directory end"))?;
+    let eocd = &tail[position..];
+    let (directory_size, directory_offset) = (u32_at(eocd, 12), u32_at(eocd, 16));
+    if directory_size != u32::MAX && directory_offset != u32::MAX {
+        return Ok((directory_offset as u64, directory_size as u64));
+    }
+
+    // zip64 archives record the central directory in a separate record, found via a locator.
+    let eocd_offset = start + position as u64;
+    let locator_offset = eocd_offset.checked_sub(ZIP64_LOCATOR_SIZE)
+        .ok_or(ZipError::InvalidArchive("Could not find zip64 locator"))?;
+    let locator = read_slice(blob, locator_offset, ZIP64_LOCATOR_SIZE).await?;
+    if u32_at(&locator, 0) != ZIP64_LOCATOR_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Could not find zip64 locator").into());
+    }
+    let record = read_slice(blob, u64_at(&locator, 8), 56).await?;
+    if record.len() < 56 || u32_at(&record, 0) != ZIP64_EOCD_SIGNATURE {
+        return Err(ZipError::InvalidArchive("Invalid zip64 central directory end").into());
+    }
+    Ok((u64_at(&record, 48), u64_at(&record, 40)))
+}
+
+/// Decodes the name of an entry. Names are UTF-8 if the UTF-8 flag is set, and are
+/// otherwise in the encoding of the system which created the archive, which is
+/// assumed to be UTF-8 if the name is valid UTF-8, then Shift_JIS (i.e. archives
+/// created on Japanese Windows), and otherwise the CP437 of the zip specification.
+fn decode_name(bytes: &[u8], flags: u16) -> String {
+    if flags & UTF8_FLAG != 0 {
+        return String::from_utf8_lossy(bytes).into_owned();
+    }
+    if let Ok(name) = std::str::from_utf8(bytes) {
+        return name.to_owned();
+    }
+    if let Some(name) = encoding_rs::SHIFT_JIS.decode_without_bom_handling_and_without_replacement(bytes) {
+        return name.into_owned();
+    }
+    bytes.iter().map(|&byte| match byte {
+        0..=0x7F => byte as char,
+        _ => CP437[byte as usize - 0x80],
+    }).collect()
+}
+
+/// The characters of the bytes 0x80 to 0xFF of code page 437.
+const CP437: [char; 128] = [
+    'Ç', 'ü', 'é', 'â', 'ä', 'à', 'å', 'ç', 'ê', 'ë', 'è', 'ï', 'î', 'ì', 'Ä', 'Å',
+    'É', 'æ', 'Æ', 'ô', 'ö', 'ò', 'û', 'ù', 'ÿ', 'Ö', 'Ü', '¢', '£', '¥', '₧', 'ƒ',
+    'á', 'í', 'ó', 'ú', 'ñ', 'Ñ', 'ª', 'º', '¿', '⌐', '¬', '½', '¼', '¡', '«', '»',
+    '░', '▒', '▓', '│', '┤', '╡', '╢', '╖', '╕', '╣', '║', '╗', '╝', '╜', '╛', '┐',
+    '└', '┴', '┬', '├', '─', '┼', '╞', '╟', '╚', '╔', '╩', '╦', '╠', '═', '╬', '╧',
+    '╨', '╤', '╥', '╙', '╘', '╒', '╓', '╫', '╪', '┘', '┌', '█', '▄', '▌', '▐', '▀',
+    'α', 'ß', 'Γ', 'π', 'Σ', 'σ', 'µ', 'τ', 'Φ', 'Θ', 'Ω', 'δ', '∞', 'φ', 'ε', '∩',
+    '≡', '±', '≥', '≤', '⌠', '⌡', '÷', '≈', '°', '∙', '·', '√', 'ⁿ', '²', '■', '\u{A0}',
+];
+
+/// Entries which exceed the limits of the zip format store their values in the
+/// zip64 extra field, in the order (size, compressed size, header offset).
+fn read_zip64_extra_field(entry: &mut ZipEntry, mut extra: &[u8]) {
+    while extra.len() >= 4 {
+        let (id, len) = (u16_at(extra, 0), u16_at(extra, 2) as usize);
+        let Some(mut field) = extra.get(4..4 + len) else { return; };
+        if id == ZIP64_EXTRA_FIELD {
+            let values = [&mut entry.size, &mut entry.compressed_size, &mut entry.header_offset];
+            for value in values.into_iter().filter(|value| **value == u32::MAX as u64) {
+                if field.len() < 8 { break; }
+                *value = u64_at(field, 0);
+                field = &field[8..];
+            }
+            return;
+        }
+        extra = &extra[4 + len..];
+    }
+}
+
+async fn read_slice(blob: &gloo_file::Blob, offset: u64, size: u64) -> crate::Result<Vec<u8>> {
+    let end = offset.saturating_add(size).min(blob.size());
+    Ok(gloo_file_read(&blob.slice(offset, end)).await?)
+}
+
+fn put_u16(bytes: &mut Vec<u8>, value: u16) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u32(bytes: &mut Vec<u8>, value: u32) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn put_u64(bytes: &mut Vec<u8>, value: u64) {
+    bytes.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap_or_default())
+}
+
+fn u64_at(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap_or_default())
+}
+
+/// Decompresses a deflated entry, reading no more than its size in the header.
+/// The size is not trusted for the initial allocation beyond the most that deflate can
+/// expand the data, or `MAX_INITIAL_CAPACITY`.
+fn inflate(compressed: &[u8], size: u64) -> Result<Vec<u8>, ZipError> {
+    let capacity = size.min(compressed.len() as u64 * MAX_DEFLATE_RATIO).min(MAX_INITIAL_CAPACITY);
+    let mut data = Vec::with_capacity(capacity as usize);
+    DeflateDecoder::new(compressed).take(size).read_to_end(&mut data).map_err(ZipError::Io)?;
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::DeflateEncoder, Compression};
+
+    use super::*;
+
+    #[test]
+    fn decodes_names() {
+        assert_eq!(decode_name("巻/001.jpg".as_bytes(), UTF8_FLAG), "巻/001.jpg");
+        assert_eq!(decode_name("巻/001.jpg".as_bytes(), 0), "巻/001.jpg");
+        // 巻 in Shift_JIS.
+        assert_eq!(decode_name(&[0x8A, 0xAA, b'/', b'1'], 0), "巻/1");
+        // 0xA0 is not valid Shift_JIS.
+        assert_eq!(decode_name(&[0xA0, b'1'], 0), "á1");
+    }
+
+    #[test]
+    fn inflates_no_more_than_the_size() {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&[7; 1000]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        assert_eq!(inflate(&compressed, 1000).unwrap(), [7; 1000]);
+        assert_eq!(inflate(&compressed, 10).unwrap().len(), 10);
+        assert_eq!(inflate(&compressed, 1 << 40).unwrap().len(), 1000);
+    }
+}
@@ -1,21 +1,34 @@
+use std::future::Future;
+use std::pin::Pin;
 use std::rc::Rc;
 
-use rexie::{ObjectStore, Rexie, Store, Transaction, TransactionMode};
+use gloo_console as console;
+use rexie::{Index, KeyRange, ObjectStore, Rexie, Store, Transaction, TransactionMode};
+use serde::{Deserialize, Serialize};
 use serde_wasm_bindgen::from_value as serde_from_wasm;
 use wasm_bindgen::JsValue;
 use yew::AttrValue;
 
 use crate::errors::Result;
 use crate::models::{PageImage, PageOcr, Settings, VolumeId, VolumeMetadata};
+use crate::utils::{iso_timestamp, timestamp};
+use crate::utils::search::SearchEntry;
 
 const G: &str = "global";
+const N: &str = "search";
 const O: &str = "ocr";
 const P: &str = "pages";
+const S: &str = "snapshots";
+const T: &str = "thumbnails";
 const V: &str = "volumes";
+const GRAMS_INDEX: &str = "grams";
+const VOLUME_UUID_INDEX: &str = "volume_uuid";
+/// How long an import may run before it is assumed to have been interrupted.
+const INCOMPLETE_IMPORT_MS: u64 = 24 * 60 * 60 * 1000;
 
 
 /// Creates the IndexedDB instance used by this App.
-/// There are three ObjectStores: volumes, pages, & ocr.
+/// There are six ObjectStores: volumes, pages, ocr, snapshots, thumbnails, & search.
 ///   - `volumes` contains JSON data and is the contents of the
 ///     mokuro-metadata.json file within the zip archive.
 ///     It's indexed by auto-incremented keys, meaning that multiple
@@ -27,22 +40,260 @@ const V: &str = "volumes";
 ///     within the _ocr directory of the zip archive.
 ///     Each row is keyed by (volume_id, page_name), where page_name
 ///     is the associated image for the ocr output.
+///   - `snapshots` contains the ocr output as it was when the volume was
+///     imported, keyed the same as `ocr`. This is the common ancestor
+///     used when merging the edits of two copies of a volume.
+///   - `thumbnails` contains a small image of the cover of each volume,
+///     keyed by volume_id, which is displayed in the library.
+///   - `search` contains the character n-grams of the text of each page,
+///     keyed the same as `ocr`, and is indexed by each of its n-grams
+///     (a multiEntry index), i.e. it is an inverted index of the text.
+///     It is written alongside `ocr`, and can be rebuilt from it.
 ///
 /// Note: `pages` and `ocr` were into separate stores because the rows
 ///       of `pages` will never change, but `ocr` might be updated.
 ///       IndexedDB does not support partial updates.
-pub async fn create_database() -> rexie::Result<Rexie> {
-    let rexie = Rexie::builder("mokuro")
-        .version(2)
-        .add_object_store(ObjectStore::new(G))
-        .add_object_store(ObjectStore::new(V).key_path("id").auto_increment(true))
-        .add_object_store(ObjectStore::new(P))
-        .add_object_store(ObjectStore::new(O))
-        .build()
-        .await?;
+///
+/// The schema is built up by the ordered list of MIGRATIONS, and the database
+/// version is that of the latest migration. Once the database is open, the row
+/// transforms of any migrations which have not been applied yet are run in order,
+/// and each applied migration is recorded in the `global` store.
+pub async fn create_database() -> Result<Rexie> {
+    let version = MIGRATIONS.last().map_or(1, |migration| migration.version);
+    let builder = object_stores().into_iter()
+        .fold(Rexie::builder("mokuro").version(version), |builder, store| builder.add_object_store(store));
+    let rexie = builder.build().await?;
+    run_migrations(&rexie).await?;
+    delete_incomplete_imports(&rexie).await?;
     Ok(rexie)
 }
 
+type Transform = for<'a> fn(&'a Rexie) -> Pin<Box<dyn Future<Output = Result<()>> + 'a>>;
+
+/// A version of the database schema.
+/// Rexie creates any missing stores and indexes (and deletes any which are no longer
+/// declared) when the version increases, so each migration only declares its additions.
+struct Migration {
+    version: u32,
+    description: &'static str,
+    /// The stores added by this version, as (name, key_path, auto_increment).
+    stores: &'static [(&'static str, Option<&'static str>, bool)],
+    /// The indexes added by this version, as (store, index name, key_path, multi_entry).
+    indexes: &'static [(&'static str, &'static str, &'static str, bool)],
+    /// Rewrites the existing rows once the stores and indexes have been upgraded.
+    transform: Option<Transform>,
+}
+
+/// The history of the database schema, in order. Versions must only ever be appended.
+/// Databases created before migrations were recorded are at version 2 or 3.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 2,
+        description: "global, volumes, pages & ocr stores",
+        stores: &[(G, None, false), (V, Some("id"), true), (P, None, false), (O, None, false)],
+        indexes: &[],
+        transform: None,
+    },
+    Migration {
+        version: 3,
+        description: "snapshots store of the ocr as imported",
+        stores: &[(S, None, false)],
+        indexes: &[],
+        transform: None,
+    },
+    Migration {
+        version: 4,
+        description: "index volumes by volume_uuid",
+        stores: &[],
+        indexes: &[(V, VOLUME_UUID_INDEX, "volume_uuid", false)],
+        transform: None,
+    },
+    Migration {
+        version: 5,
+        description: "backfill the wide pages of volumes",
+        stores: &[],
+        indexes: &[],
+        transform: Some(|db| Box::pin(backfill_wide_pages(db))),
+    },
+    Migration {
+        version: 6,
+        description: "thumbnails store of the volume covers",
+        stores: &[(T, None, false)],
+        indexes: &[],
+        transform: None,
+    },
+    Migration {
+        version: 7,
+        description: "search store of the n-grams of each page",
+        stores: &[(N, None, false)],
+        indexes: &[(N, GRAMS_INDEX, "grams", true)],
+        transform: Some(|db| Box::pin(index_library(db))),
+    },
+];
+
+/// A migration which has been applied to the database, as recorded in the `global` store.
+#[derive(Serialize, Deserialize)]
+struct MigrationRecord {
+    version: u32,
+    description: String,
+    applied_at: String,
+}
+
+/// The stores and indexes declared by every migration.
+fn object_stores() -> Vec<ObjectStore> {
+    let stores = MIGRATIONS.iter().flat_map(|migration| migration.stores.iter());
+    stores.map(|&(name, key_path, auto_increment)| {
+        let mut store = ObjectStore::new(name);
+        if let Some(key_path) = key_path {
+            store = store.key_path(key_path).auto_increment(auto_increment);
+        }
+        let indexes = MIGRATIONS.iter().flat_map(|migration| migration.indexes.iter());
+        indexes.filter(|(store_name, ..)| *store_name == name)
+            .fold(store, |store, &(_, index, key_path, multi_entry)| {
+                store.add_index(Index::new(index, key_path).multi_entry(multi_entry))
+            })
+    }).collect()
+}
+
+/// Runs the transforms of the migrations which have not been recorded as applied.
+/// Each migration is recorded once its transform succeeds, so a failed
+/// transform is retried the next time the database is opened.
+async fn run_migrations(db: &Rexie) -> Result<()> {
+    let key = JsValue::from_str("migrations");
+    let value = db.transaction(&[G], TransactionMode::ReadOnly)?.store(G)?.get(&key).await?;
+    let mut history: Vec<MigrationRecord> =
+        if value.is_undefined() { vec![] } else { serde_from_wasm(value)? };
+    let applied = history.iter().map(|record| record.version).max().unwrap_or_default();
+
+    for migration in MIGRATIONS.iter().filter(|migration| migration.version > applied) {
+        if let Some(transform) = migration.transform {
+            transform(db).await?;
+        }
+        history.push(MigrationRecord {
+            version: migration.version,
+            description: migration.description.to_string(),
+            applied_at: iso_timestamp(),
+        });
+        let txn = db.transaction(&[G], TransactionMode::ReadWrite)?;
+        txn.store(G)?.put(&serde_to_wasm(&history)?, Some(&key)).await?;
+        txn.done().await?;
+    }
+    Ok(())
+}
+
+/// Volumes uploaded before wide pages were tracked are given them from the OCR dimensions.
+/// A volume whose rows cannot be read is skipped (and keeps no wide pages), rather than
+/// failing the upgrade, as the database could not then be opened at all.
+async fn backfill_wide_pages(db: &Rexie) -> Result<()> {
+    for volume in read_volumes(db).await? {
+        let (id, title) = (volume.id, volume.title.clone());
+        if let Err(error) = backfill_volume(db, volume).await {
+            console::warn!(format!("skipped the wide pages of volume {id} ({title}): {error}"));
+        }
+    }
+    Ok(())
+}
+
+async fn backfill_volume(db: &Rexie, mut volume: VolumeMetadata) -> Result<()> {
+    if volume.wide_pages.is_some() { return Ok(()); }
+    let txn = db.transaction(&[V, O], TransactionMode::ReadWrite)?;
+    let (volumes, ocr) = (txn.store(V)?, txn.store(O)?);
+    let id = volume.id.into();
+    let mut wide_pages = vec![];
+    for (index, (page_name, _)) in volume.pages.iter().enumerate() {
+        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+        let page_ocr: PageOcr = serde_from_wasm(ocr.get(&key).await?)?;
+        if page_ocr.is_wide() { wide_pages.push(index); }
+    }
+    volume.wide_pages = Some(wide_pages);
+    volumes.put(&serde_to_wasm(&volume)?, None).await?;
+    txn.done().await?;
+    Ok(())
+}
+
+/// Replaces the search index with the n-grams of the OCR of every volume.
+/// Each volume is indexed within its own transaction, and a volume whose rows
+/// cannot be read is skipped (i.e. it is not searchable until it is edited).
+async fn index_library(db: &Rexie) -> Result<()> {
+    let txn = db.transaction(&[N], TransactionMode::ReadWrite)?;
+    txn.store(N)?.clear().await?;
+    txn.done().await?;
+
+    for volume in read_volumes(db).await? {
+        if let Err(error) = index_volume(db, &volume).await {
+            console::warn!(format!("skipped indexing volume {} ({}): {error}", volume.id, volume.title));
+        }
+    }
+    Ok(())
+}
+
+async fn index_volume(db: &Rexie, volume: &VolumeMetadata) -> Result<()> {
+    let txn = db.transaction(&[O, N], TransactionMode::ReadWrite)?;
+    let (ocr, search) = (txn.store(O)?, txn.store(N)?);
+    let id = volume.id.into();
+    // The OCR is read before anything is written, so that a bad row leaves the volume unindexed.
+    let mut entries = Vec::with_capacity(volume.pages.len());
+    for (page_name, _) in volume.pages.iter() {
+        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+        let page_ocr: PageOcr = serde_from_wasm(ocr.get(&key).await?)?;
+        entries.push((key, search_entry(volume.id, page_name, &page_ocr)?));
+    }
+    for (key, entry) in entries {
+        search.put(&entry, Some(&key)).await?;
+    }
+    txn.done().await?;
+    Ok(())
+}
+
+/// Deletes the volumes whose import was interrupted before all of their pages were written.
+/// Imports which started recently are skipped, as they may still be running in another tab.
+async fn delete_incomplete_imports(db: &Rexie) -> Result<()> {
+    let now = timestamp();
+    let interrupted = |volume: &VolumeMetadata| volume.importing && volume.import_started
+        .is_none_or(|started| now.saturating_sub(started) > INCOMPLETE_IMPORT_MS);
+    for volume in read_volumes(db).await?.into_iter().filter(interrupted) {
+        console::warn!(format!("deleting the incomplete import of volume {} ({})", volume.id, volume.title));
+        delete_volume(db, volume.id).await?;
+    }
+    Ok(())
+}
+
+/// The volumes which can be deserialized, including any which are still being imported.
+/// Rows which cannot be deserialized are skipped, and logged.
+async fn read_volumes(db: &Rexie) -> Result<Vec<VolumeMetadata>> {
+    let values = db.transaction(&[V], TransactionMode::ReadOnly)?
+        .store(V)?
+        .get_all(None, None, None, None).await?;
+    Ok(values.into_iter().filter_map(|(key, value)| {
+        serde_from_wasm(value)
+            .map_err(|error| console::warn!(format!("skipped volume {key:?}: {error}")))
+            .ok()
+    }).collect())
+}
+
+/// The row of the `search` store for the OCR of a page.
+pub fn search_entry(volume_id: VolumeId, page_name: &AttrValue, ocr: &PageOcr) -> Result<JsValue> {
+    serde_to_wasm(&SearchEntry::new(volume_id, page_name, ocr))
+}
+
+/// The range of (volume_id, page_name) keys which belong to the volume.
+/// Arrays are ordered after strings, so [id, []] bounds every [id, page_name].
+fn volume_key_range(volume_id: VolumeId) -> Result<KeyRange> {
+    let id: JsValue = volume_id.into();
+    let lower = js_sys::Array::of1(&id);
+    let upper = js_sys::Array::of2(&id, &js_sys::Array::new());
+    Ok(KeyRange::bound(&lower, &upper, false, false)?)
+}
+
+/// Converts a value into a JsValue which can be stored in IndexedDB.
+/// Maps (i.e. the flattened fields which are not modelled by this app)
+/// are serialized as plain objects instead of the default ES Maps, as the
+/// `volumes` key path cannot be resolved on an ES Map.
+pub fn serde_to_wasm<T: Serialize + ?Sized>(value: &T) -> Result<JsValue> {
+    let serializer = serde_wasm_bindgen::Serializer::new().serialize_maps_as_objects(true);
+    Ok(value.serialize(&serializer)?)
+}
+
 pub async fn get_settings(db: &Rc<Rexie>) -> Result<Settings> {
     let settings = db.transaction(&[G], TransactionMode::ReadOnly)?
         .store(G)?
@@ -52,7 +303,7 @@ pub async fn get_settings(db: &Rc<Rexie>) -> Result<Settings> {
 }
 
 pub async fn put_settings(db: &Rc<Rexie>, settings: &Settings) -> Result<()> {
-    let value = serde_wasm_bindgen::to_value(settings)?;
+    let value = serde_to_wasm(settings)?;
     db.transaction(&[G], TransactionMode::ReadWrite)?
         .store(G)?
         .put(&value, Some(&JsValue::from_str("settings"))).await?;
@@ -61,14 +312,13 @@ pub async fn put_settings(db: &Rc<Rexie>, settings: &Settings) -> Result<()> {
 
 /// Start a transaction with the `pages` and `ocr` stores for bulk insertion.
 /// This method is just to keep all string references to the stores in this file.
-pub fn start_bulk_write_txn(db: &Rc<Rexie>) -> Result<(Transaction, Store, Store)> {
-    let txn = db.transaction(&[P, O], TransactionMode::ReadWrite)?;
-    let pages = txn.store(P)?;
-    let ocr = txn.store(O)?;
-    Ok((txn, pages, ocr))
+/// Returns the `pages`, `ocr`, `snapshots` & `search` stores, for writing imported pages.
+pub fn start_bulk_write_txn(db: &Rc<Rexie>) -> Result<(Transaction, [Store; 4])> {
+    let txn = db.transaction(&[P, O, S, N], TransactionMode::ReadWrite)?;
+    let stores = [txn.store(P)?, txn.store(O)?, txn.store(S)?, txn.store(N)?];
+    Ok((txn, stores))
 }
 
-#[allow(dead_code)]
 pub async fn get_page(db: Rc<Rexie>, volume_id: VolumeId, name: AttrValue) -> Result<PageImage> {
     let key = js_sys::Array::of2(&volume_id.into(), &name.as_str().into());
     let txn = db.transaction(&[P], TransactionMode::ReadOnly)?;
@@ -76,10 +326,134 @@ pub async fn get_page(db: Rc<Rexie>, volume_id: VolumeId, name: AttrValue) -> Re
     Ok(pages.get(&key).await?.into())
 }
 
-pub async fn put_ocr(db: &Rc<Rexie>, ocr: &PageOcr, key: &JsValue) -> Result<()> {
-    let value = serde_wasm_bindgen::to_value(ocr)?;
-    let txn = db.transaction(&[O], TransactionMode::ReadWrite)?;
-    txn.store(O)?.put(&value, Some(key)).await?;
+/// Thumbnails are created when a volume is imported, so volumes imported before
+/// thumbnails existed do not have one until it is backfilled.
+pub async fn get_thumbnail(db: &Rc<Rexie>, volume_id: VolumeId) -> Result<Option<PageImage>> {
+    let value = db.transaction(&[T], TransactionMode::ReadOnly)?
+        .store(T)?
+        .get(&volume_id.into()).await?;
+    Ok(if value.is_undefined() { None } else { Some(value.into()) })
+}
+
+pub async fn put_thumbnail(db: &Rc<Rexie>, volume_id: VolumeId, thumbnail: &PageImage) -> Result<()> {
+    let txn = db.transaction(&[T], TransactionMode::ReadWrite)?;
+    txn.store(T)?.put(thumbnail.as_ref(), Some(&volume_id.into())).await?;
+    txn.done().await?;
+    Ok(())
+}
+
+pub async fn get_ocr(db: &Rc<Rexie>, key: &JsValue) -> Result<PageOcr> {
+    let value = db.transaction(&[O], TransactionMode::ReadOnly)?
+        .store(O)?
+        .get(key).await?;
+    Ok(serde_from_wasm(value)?)
+}
+
+/// Writes the OCR of a page, and updates the search index of the page to match.
+pub async fn put_ocr(db: &Rc<Rexie>, volume_id: VolumeId, page_name: &AttrValue, ocr: &PageOcr) -> Result<()> {
+    let key = js_sys::Array::of2(&volume_id.into(), &page_name.as_str().into());
+    let value = serde_to_wasm(ocr)?;
+    let txn = db.transaction(&[O, N], TransactionMode::ReadWrite)?;
+    txn.store(O)?.put(&value, Some(&key)).await?;
+    txn.store(N)?.put(&search_entry(volume_id, page_name, ocr)?, Some(&key)).await?;
+    txn.done().await?;
+    Ok(())
+}
+
+/// Rebuilds the search index from the OCR of every volume, e.g. if it has become out of date.
+pub async fn rebuild_search_index(db: &Rc<Rexie>) -> Result<()> {
+    index_library(db).await
+}
+
+/// Retrieves the search index of every page which contains all of the (sorted) n-grams.
+/// Only the pages containing the rarest n-gram are read, which are then filtered by the others.
+pub async fn get_search_candidates(db: &Rc<Rexie>, grams: &[String]) -> Result<Vec<SearchEntry>> {
+    let txn = db.transaction(&[N], TransactionMode::ReadOnly)?;
+    let index = txn.store(N)?.index(GRAMS_INDEX)?;
+    let mut rarest = None;
+    for gram in grams.iter() {
+        let key = KeyRange::only(&JsValue::from_str(gram))?;
+        let count = index.count(Some(&key)).await?;
+        if count == 0 { return Ok(vec![]); }
+        if rarest.as_ref().is_none_or(|(_, least)| count < *least) {
+            rarest = Some((key, count));
+        }
+    }
+    let Some((key, _)) = rarest else { return Ok(vec![]); };
+    let values = index.get_all(Some(&key), None, None, None).await?;
+    let mut entries = Vec::with_capacity(values.len());
+    for (_k, value) in values.into_iter() {
+        let entry: SearchEntry = serde_from_wasm(value)?;
+        if entry.contains(grams) { entries.push(entry); }
+    }
+    Ok(entries)
+}
+
+/// The number of pages of the volume which are in the search index.
+pub async fn count_search_entries(db: &Rc<Rexie>, volume_id: VolumeId) -> Result<usize> {
+    let range = volume_key_range(volume_id)?;
+    let txn = db.transaction(&[N], TransactionMode::ReadOnly)?;
+    Ok(txn.store(N)?.count(Some(&range)).await? as usize)
+}
+
+/// Retrieves the OCR data of every page of a volume, in page order.
+pub async fn get_volume_ocr(db: &Rc<Rexie>, volume: &VolumeMetadata) -> Result<Vec<PageOcr>> {
+    let txn = db.transaction(&[O], TransactionMode::ReadOnly)?;
+    let store = txn.store(O)?;
+    let id = volume.id.into();
+    let mut result = Vec::with_capacity(volume.pages.len());
+    for (page_name, _) in volume.pages.iter() {
+        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+        result.push(serde_from_wasm(store.get(&key).await?)?);
+    }
+    Ok(result)
+}
+
+/// Retrieves the OCR data of every page of a volume as it was imported, in page order.
+/// Pages imported before snapshots were recorded do not have a snapshot.
+pub async fn get_volume_snapshots(db: &Rc<Rexie>, volume: &VolumeMetadata) -> Result<Vec<Option<PageOcr>>> {
+    let txn = db.transaction(&[S], TransactionMode::ReadOnly)?;
+    let store = txn.store(S)?;
+    let id = volume.id.into();
+    let mut result = Vec::with_capacity(volume.pages.len());
+    for (page_name, _) in volume.pages.iter() {
+        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+        let value = store.get(&key).await?;
+        result.push(if value.is_undefined() { None } else { Some(serde_from_wasm(value)?) });
+    }
+    Ok(result)
+}
+
+/// Replaces the import snapshots of every page of a volume, in page order.
+/// Pages without a snapshot have any existing snapshot removed.
+pub async fn put_volume_snapshots(db: &Rc<Rexie>, volume: &VolumeMetadata, snapshots: &[Option<PageOcr>]) -> Result<()> {
+    let txn = db.transaction(&[S], TransactionMode::ReadWrite)?;
+    let store = txn.store(S)?;
+    let id = volume.id.into();
+    for ((page_name, _), snapshot) in volume.pages.iter().zip(snapshots) {
+        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+        match snapshot {
+            Some(snapshot) => { store.put(&serde_to_wasm(snapshot)?, Some(&key)).await?; }
+            None => store.delete(&key).await?,
+        }
+    }
+    txn.done().await?;
+    Ok(())
+}
+
+/// Writes the volume and the OCR data of its pages within a single transaction,
+/// so that either all of the changes are saved or none of them are.
+pub async fn put_volume_and_ocr(db: &Rc<Rexie>, volume: &VolumeMetadata, ocr: &[(AttrValue, PageOcr)]) -> Result<()> {
+    let txn = db.transaction(&[V, O, N], TransactionMode::ReadWrite)?;
+    let (store, search) = (txn.store(O)?, txn.store(N)?);
+    let id = volume.id.into();
+    for (page_name, page_ocr) in ocr.iter() {
+        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+        store.put(&serde_to_wasm(page_ocr)?, Some(&key)).await?;
+        search.put(&search_entry(volume.id, page_name, page_ocr)?, Some(&key)).await?;
+    }
+    txn.store(V)?.put(&serde_to_wasm(volume)?, None).await?;
+    txn.done().await?;
     Ok(())
 }
 
@@ -91,7 +465,7 @@ pub async fn get_page_and_ocr(db: &Rc<Rexie>, key: &JsValue) -> Result<(PageImag
 
     let ocr = txn.store(O)?;
     let ocr_value = ocr.get(key).await?;
-    Ok((page_value, serde_wasm_bindgen::from_value(ocr_value)?))
+    Ok((page_value, serde_from_wasm(ocr_value)?))
 }
 
 pub async fn get_volume(db: &Rc<Rexie>, volume_id: VolumeId) -> Result<VolumeMetadata> {
@@ -101,34 +475,54 @@ pub async fn get_volume(db: &Rc<Rexie>, volume_id: VolumeId) -> Result<VolumeMet
     Ok(serde_from_wasm(value)?)
 }
 
-#[allow(dead_code)]
+/// Retrieves every volume which has been fully imported.
 pub async fn get_all_volumes(db: Rc<Rexie>) -> Result<Vec<VolumeMetadata>> {
+    let volumes = read_volumes(&db).await?;
+    Ok(volumes.into_iter().filter(|volume| !volume.importing).collect())
+}
+
+/// Retrieves every copy of the volume with the given uuid, most recently modified first.
+pub async fn get_volumes_by_uuid(db: &Rc<Rexie>, volume_uuid: &str) -> Result<Vec<VolumeMetadata>> {
+    let key = KeyRange::only(&JsValue::from_str(volume_uuid))?;
     let values = db.transaction(&[V], TransactionMode::ReadOnly)?
         .store(V)?
-        .get_all(None, None, None, None).await?;
-    Ok(values.into_iter().filter_map(|(_k, v)| serde_from_wasm(v).ok()).collect())
+        .index(VOLUME_UUID_INDEX)?
+        .get_all(Some(&key), None, None, None).await?;
+    let mut volumes: Vec<VolumeMetadata> = values.into_iter()
+        .filter_map(|(_k, v)| serde_from_wasm::<VolumeMetadata>(v).ok())
+        .filter(|volume| !volume.importing)
+        .collect();
+    volumes.sort_by(|a, b| b.modified_at.cmp(&a.modified_at));
+    Ok(volumes)
 }
 
-pub async fn get_all_volumes_with_covers(db: &Rc<Rexie>) -> Result<Vec<(VolumeMetadata, PageImage)>> {
-    let txn = db.transaction(&[V, P], TransactionMode::ReadOnly)?;
+/// Calculates the storage used by each volume as (volume, image bytes, ocr bytes).
+/// The size of the OCR data is the length of its JSON, which approximates how it is stored.
+pub async fn get_volume_sizes(db: &Rc<Rexie>) -> Result<Vec<(VolumeMetadata, u64, u64)>> {
+    let txn = db.transaction(&[V, P, O], TransactionMode::ReadOnly)?;
     let values = txn.store(V)?.get_all(None, None, None, None).await?;
-    let pages = txn.store(P)?;
+    let (pages, ocr) = (txn.store(P)?, txn.store(O)?);
 
     let mut result = Vec::with_capacity(values.len());
     for (_k, v) in values.into_iter() {
         let volume: VolumeMetadata = serde_from_wasm(v)?;
-        let key = js_sys::Array::of2(&volume.id.into(), &volume.cover().as_str().into());
-        let cover: PageImage = pages.get(&key).await?.into();
-        result.push((volume, cover));
+        let range = volume_key_range(volume.id)?;
+        let images = pages.get_all(Some(&range), None, None, None).await?.into_iter()
+            .map(|(_k, v)| PageImage::from(v).size())
+            .sum();
+        let ocr_size = ocr.get_all(Some(&range), None, None, None).await?.into_iter()
+            .filter_map(|(_k, v)| js_sys::JSON::stringify(&v).ok()?.as_string())
+            .map(|json| json.len() as u64)
+            .sum();
+        result.push((volume, images, ocr_size));
     }
     Ok(result)
 }
 
-
 /// put_config inserts/updates a row within the "volumes" ObjectStore.
 /// If `volume.id` is set, the object is updated.
 pub async fn put_volume(db: &Rc<Rexie>, volume: &VolumeMetadata) -> Result<VolumeId> {
-    let config = serde_wasm_bindgen::to_value(volume)?;
+    let config = serde_to_wasm(volume)?;
     let txn = db.transaction(&[V], TransactionMode::ReadWrite)?;
     let volume_id = txn.store(V)?.put(&config, None).await?;
     txn.done().await?;
@@ -137,15 +531,17 @@ pub async fn put_volume(db: &Rc<Rexie>, volume: &VolumeMetadata) -> Result<Volum
 
 /// delete_volume cascade deletes the volume with matching volume_id,
 ///   removing all images and ocr data.
-pub async fn delete_volume(db: &Rc<Rexie>, volume_id: VolumeId) -> Result<()> {
-    let volume = get_volume(db, volume_id).await?;
-    let txn = db.transaction(&[V, O, P], TransactionMode::ReadWrite)?;
+/// The pages are deleted by key range rather than by the page names listed
+/// in the volume, so that partially imported volumes are cleaned up as well.
+pub async fn delete_volume(db: &Rexie, volume_id: VolumeId) -> Result<()> {
+    let txn = db.transaction(&[V, O, P, S, T, N], TransactionMode::ReadWrite)?;
     let id = volume_id.into();
-    for (page_name, _) in volume.pages.iter() {
-        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
-        txn.store(P)?.delete(&key).await?;
-        txn.store(O)?.delete(&key).await?;
-    }
+    let pages = volume_key_range(volume_id)?;
+    txn.store(P)?.delete(pages.as_ref()).await?;
+    txn.store(O)?.delete(pages.as_ref()).await?;
+    txn.store(S)?.delete(pages.as_ref()).await?;
+    txn.store(N)?.delete(pages.as_ref()).await?;
+    txn.store(T)?.delete(&id).await?;
     txn.store(V)?.delete(&id).await?;
     txn.done().await?;
     Ok(())
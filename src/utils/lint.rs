@@ -0,0 +1,161 @@
+//! A rule-based linter for the characters which OCR commonly confuses, e.g. カ/力 or ー/一.
+//!
+//! Each rule flags a piece of text within a context (see `LintContext`), as most of these
+//! characters are only suspicious next to certain others, e.g. 力 between katakana.
+//! The issues are `replace::Match`es, so that they can be fixed the same way as a replacement.
+use yew::AttrValue;
+
+use crate::models::{CharClass, LintContext, LintRule, PageOcr};
+use crate::utils::replace::Match;
+
+/// A suspicious piece of text, and the rule which flagged it.
+#[derive(Clone, PartialEq)]
+pub struct Issue {
+    pub rule: AttrValue,
+    pub fix: Match,
+}
+
+impl CharClass {
+    pub fn contains(&self, c: char) -> bool {
+        match self {
+            Self::Hiragana => matches!(c, '\u{3041}'..='\u{309F}'),
+            Self::Katakana => matches!(c, '\u{30A1}'..='\u{30FF}' | '\u{31F0}'..='\u{31FF}' | '\u{FF66}'..='\u{FF9F}'),
+            Self::Kana => Self::Hiragana.contains(c) || Self::Katakana.contains(c),
+            Self::Kanji => matches!(c, '\u{3005}' | '\u{3400}'..='\u{4DBF}' | '\u{4E00}'..='\u{9FFF}' | '\u{F900}'..='\u{FAFF}'),
+            Self::Japanese => {
+                Self::Kana.contains(c) || Self::Kanji.contains(c)
+                    || matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF01}'..='\u{FF65}')
+            }
+            Self::Ascii => c.is_ascii(),
+            Self::IRow => "きぎしじちぢにひびぴみりキギシジチヂニヒビピミリ".contains(c),
+        }
+    }
+}
+
+impl LintContext {
+    /// Whether the neighbours of the text (if any) match this context.
+    pub fn matches(&self, before: Option<char>, after: Option<char>) -> bool {
+        let is = |c: Option<char>, class: &CharClass| c.is_some_and(|c| class.contains(c));
+        match self {
+            Self::Always => true,
+            Self::Neighbour(class) => is(before, class) || is(after, class),
+            Self::Between(class) => is(before, class) && is(after, class),
+            Self::After(class) => is(before, class),
+            Self::NotAfter(class) => !is(before, class),
+        }
+    }
+}
+
+/// Finds the issues within the text of the blocks of the page, using the enabled rules.
+/// Where the rules overlap, only the first rule (in order) is reported.
+pub fn lint_page(rules: &[LintRule], page: usize, page_name: &AttrValue, ocr: &PageOcr) -> Vec<Issue> {
+    let rules: Vec<(&LintRule, Vec<char>)> = rules.iter()
+        .filter(|rule| rule.enabled && !rule.find.is_empty())
+        .map(|rule| (rule, rule.find.chars().collect()))
+        .collect();
+    let mut issues = vec![];
+    for block in ocr.blocks.iter() {
+        for (index, line) in block.lines.iter().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            let mut found: Vec<(usize, usize, &LintRule)> = vec![];
+            for (rule, find) in rules.iter() {
+                for start in 0..chars.len() {
+                    let end = start + find.len();
+                    if !chars[start..].starts_with(find) { continue; }
+                    let before = start.checked_sub(1).map(|i| chars[i]);
+                    if !rule.context.matches(before, chars.get(end).copied()) { continue; }
+                    if found.iter().any(|(s, e, _)| start < *e && *s < end) { continue; }
+                    found.push((start, end, rule));
+                }
+            }
+            found.sort_by_key(|(start, _, _)| *start);
+
+            let units: Vec<u16> = line.encode_utf16().collect();
+            let offset = |i: usize| chars[..i].iter().map(|c| c.len_utf16()).sum::<usize>();
+            for (start, end, rule) in found {
+                let range = offset(start)..offset(end);
+                let fix = Match::new(page, page_name, block, index, &units, range, rule.replace.to_string());
+                issues.push(Issue { rule: rule.name.clone(), fix });
+            }
+        }
+    }
+    issues
+}
+
+/// Merges the imported rules into the rules, replacing the rules with the same name.
+pub fn merge_rules(rules: &mut Vec<LintRule>, imported: Vec<LintRule>) {
+    for rule in imported {
+        match rules.iter_mut().find(|r| r.name == rule.name) {
+            Some(existing) => *existing = rule,
+            None => rules.push(rule),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::OcrBlock;
+
+    fn lint(rules: &[LintRule], lines: &[&str]) -> Vec<(usize, usize, usize, String)> {
+        let lines = lines.iter().map(|line| line.to_string().into()).collect();
+        let ocr = PageOcr { blocks: vec![OcrBlock { lines, ..OcrBlock::default() }], ..PageOcr::default() };
+        lint_page(rules, 0, &"1".into(), &ocr).into_iter()
+            .map(|issue| (issue.fix.line, issue.fix.start, issue.fix.end, issue.fix.replacement))
+            .collect()
+    }
+
+    #[test]
+    fn matches_contexts() {
+        use CharClass::*;
+        let (kana, kanji, ascii) = (Some('カ'), Some('漢'), Some('a'));
+        assert!(LintContext::Always.matches(None, None));
+        assert!(LintContext::Neighbour(Katakana).matches(ascii, kana));
+        assert!(!LintContext::Neighbour(Katakana).matches(ascii, None));
+        assert!(LintContext::Between(Katakana).matches(kana, kana));
+        assert!(!LintContext::Between(Katakana).matches(kana, kanji));
+        assert!(LintContext::After(Kanji).matches(kanji, None));
+        assert!(!LintContext::After(Kanji).matches(None, kanji));
+        assert!(LintContext::NotAfter(IRow).matches(None, None));
+        assert!(!LintContext::NotAfter(IRow).matches(Some('し'), None));
+        assert!(Japanese.contains('。') && Kana.contains('ゃ') && !Kanji.contains('カ'));
+    }
+
+    #[test]
+    fn finds_issues_within_their_context() {
+        let rules = LintRule::defaults();
+        // 力 between katakana, and ー after a kanji.
+        assert_eq!(lint(&rules, &["ア力ア", "漢ー"]), [(0, 1, 2, "カ".to_owned()), (1, 1, 2, "一".to_owned())]);
+        // 力 within kanji, and ゃ after an i-row kana, are fine.
+        assert!(lint(&rules, &["協力", "しゃ"]).is_empty());
+    }
+
+    #[test]
+    fn locates_issues_by_utf16_offsets() {
+        let rules = [LintRule::new("ゃ", "ゃ", "や", LintContext::Always)];
+        // 𠮷 is two UTF-16 units.
+        assert_eq!(lint(&rules, &["𠮷ゃ"]), [(0, 2, 3, "や".to_owned())]);
+    }
+
+    #[test]
+    fn reports_only_the_first_of_overlapping_rules() {
+        let mut rules = vec![
+            LintRule::new("first", "ab", "x", LintContext::Always),
+            LintRule::new("second", "bc", "y", LintContext::Always),
+        ];
+        assert_eq!(lint(&rules, &["abc"]), [(0, 0, 2, "x".to_owned())]);
+        rules[0].enabled = false;
+        assert_eq!(lint(&rules, &["abc"]), [(0, 1, 3, "y".to_owned())]);
+    }
+
+    #[test]
+    fn merges_rules_by_name() {
+        let mut rules = vec![LintRule::new("a", "a", "b", LintContext::Always)];
+        merge_rules(&mut rules, vec![
+            LintRule::new("a", "a", "c", LintContext::Always),
+            LintRule::new("d", "d", "e", LintContext::Always),
+        ]);
+        let rules: Vec<(&str, &str)> = rules.iter().map(|r| (r.name.as_str(), r.replace.as_str())).collect();
+        assert_eq!(rules, [("a", "c"), ("d", "e")]);
+    }
+}
@@ -0,0 +1,121 @@
+//! Three-way merging of the OCR edits made to two copies of the same volume.
+//! Textboxes are matched by their uuid, and each copy is compared against the
+//! snapshot of the OCR taken when the volume was imported (the "base"):
+//!   - if only one copy changed a textbox (including creating or deleting it),
+//!     that change is applied.
+//!   - if both copies changed a textbox differently, our version is kept and
+//!     a conflict is recorded for the user to resolve in the reader.
+use crate::models::{BlockConflict, OcrBlock, PageOcr};
+
+/// Merges their copy of a page into our copy.
+/// If there is no snapshot of the page, every differing textbox is a conflict,
+/// although textboxes which only exist in one copy are still kept.
+pub fn merge_page(
+    page: usize, base: Option<&PageOcr>, ours: &PageOcr, theirs: &PageOcr,
+) -> (PageOcr, Vec<BlockConflict>) {
+    let find = |ocr: Option<&'_ PageOcr>, uuid: &str| -> Option<OcrBlock> {
+        ocr.and_then(|ocr| ocr.blocks.iter().find(|block| block.uuid == uuid)).cloned()
+    };
+
+    let mut blocks = Vec::with_capacity(ours.blocks.len());
+    let mut conflicts = vec![];
+    for block in ours.blocks.iter() {
+        let base = find(base, &block.uuid);
+        let theirs = find(Some(theirs), &block.uuid);
+        let ours = Some(block.clone());
+        if ours == theirs || base == theirs {
+            blocks.push(block.clone());
+        } else if base == ours {
+            blocks.extend(theirs);
+        } else {
+            conflicts.push(BlockConflict { page, index: blocks.len(), ours, theirs });
+            blocks.push(block.clone());
+        }
+    }
+
+    // Textboxes which were created by them, or deleted by us.
+    for block in theirs.blocks.iter().filter(|block| find(Some(ours), &block.uuid).is_none()) {
+        match find(base, &block.uuid) {
+            None => blocks.push(block.clone()),
+            Some(base) if &base == block => {}
+            Some(_) => {
+                let theirs = Some(block.clone());
+                conflicts.push(BlockConflict { page, index: blocks.len(), ours: None, theirs });
+            }
+        }
+    }
+
+    let mut merged = ours.clone();
+    merged.blocks = blocks;
+    (merged, conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(uuid: &str, text: &str) -> OcrBlock {
+        OcrBlock { uuid: uuid.to_owned().into(), lines: vec![text.to_owned().into()], ..OcrBlock::default() }
+    }
+
+    fn page(blocks: &[OcrBlock]) -> PageOcr {
+        PageOcr { blocks: blocks.to_vec(), ..PageOcr::default() }
+    }
+
+    fn texts(ocr: &PageOcr) -> Vec<&str> {
+        ocr.blocks.iter().map(|block| block.lines[0].as_str()).collect()
+    }
+
+    #[test]
+    fn takes_the_change_of_either_copy() {
+        let base = page(&[block("a", "a"), block("b", "b"), block("c", "c")]);
+        let ours = page(&[block("a", "ours"), block("b", "b"), block("c", "c")]);
+        let theirs = page(&[block("a", "a"), block("b", "theirs"), block("c", "c")]);
+        let (merged, conflicts) = merge_page(0, Some(&base), &ours, &theirs);
+        assert_eq!(texts(&merged), ["ours", "theirs", "c"]);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn keeps_ours_when_both_copies_change_a_block() {
+        let base = page(&[block("a", "a")]);
+        let ours = page(&[block("a", "ours")]);
+        let theirs = page(&[block("a", "theirs")]);
+        let (merged, conflicts) = merge_page(3, Some(&base), &ours, &theirs);
+        assert_eq!(texts(&merged), ["ours"]);
+        assert_eq!(conflicts.len(), 1);
+        assert!(conflicts[0] == BlockConflict {
+            page: 3, index: 0, ours: Some(block("a", "ours")), theirs: Some(block("a", "theirs")),
+        });
+    }
+
+    #[test]
+    fn merges_created_and_deleted_blocks() {
+        let base = page(&[block("a", "a"), block("b", "b"), block("c", "c")]);
+        // We deleted b, and they deleted c and created d.
+        let ours = page(&[block("a", "a"), block("c", "c")]);
+        let theirs = page(&[block("a", "a"), block("b", "b"), block("d", "d")]);
+        let (merged, conflicts) = merge_page(0, Some(&base), &ours, &theirs);
+        assert_eq!(texts(&merged), ["a", "d"]);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn conflicts_when_a_deleted_block_was_changed() {
+        let base = page(&[block("a", "a"), block("b", "b")]);
+        let ours = page(&[block("a", "a")]);
+        let theirs = page(&[block("a", "a"), block("b", "theirs")]);
+        let (merged, conflicts) = merge_page(0, Some(&base), &ours, &theirs);
+        assert_eq!(texts(&merged), ["a"]);
+        assert!(conflicts.len() == 1 && conflicts[0].ours.is_none() && conflicts[0].index == 1);
+    }
+
+    #[test]
+    fn conflicts_on_every_difference_without_a_snapshot() {
+        let ours = page(&[block("a", "ours"), block("b", "b")]);
+        let theirs = page(&[block("a", "theirs"), block("b", "b"), block("c", "c")]);
+        let (merged, conflicts) = merge_page(0, None, &ours, &theirs);
+        assert_eq!(texts(&merged), ["ours", "b", "c"]);
+        assert_eq!(conflicts.len(), 1);
+    }
+}
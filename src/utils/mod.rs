@@ -1,4 +1,15 @@
+use std::cmp::Ordering;
+
+pub mod backup;
+pub mod blob_zip;
 pub mod db;
+pub mod lint;
+pub mod merge;
+pub mod mokuro;
+pub mod normalize;
+pub mod replace;
+pub mod search;
+pub mod thumbnail;
 pub mod web;
 pub mod zip;
 
@@ -11,3 +22,51 @@ pub fn timestamp() -> u64 {
         Err(_) => 0
     }
 }
+
+/// Creates an ISO 8601 "timestamp" of the current time, e.g. 2024-08-01T12:00:00.000Z.
+pub fn iso_timestamp() -> String {
+    js_sys::Date::new_0().to_iso_string().into()
+}
+
+/// Compares two strings in "natural" order, where runs of digits are
+/// compared by their numeric value, i.e. "page2.jpg" < "page10.jpg".
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a, b);
+    loop {
+        let (Some(x), Some(y)) = (a.chars().next(), b.chars().next()) else {
+            return a.len().cmp(&b.len());
+        };
+        let ordering = if x.is_ascii_digit() && y.is_ascii_digit() {
+            let (digits_a, rest_a) = split_digits(a);
+            let (digits_b, rest_b) = split_digits(b);
+            (a, b) = (rest_a, rest_b);
+            let (digits_a, digits_b) = (digits_a.trim_start_matches('0'), digits_b.trim_start_matches('0'));
+            digits_a.len().cmp(&digits_b.len()).then_with(|| digits_a.cmp(digits_b))
+        } else {
+            (a, b) = (&a[x.len_utf8()..], &b[y.len_utf8()..]);
+            x.to_lowercase().cmp(y.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+}
+
+fn split_digits(s: &str) -> (&str, &str) {
+    let index = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    s.split_at(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_naturally() {
+        let mut names = ["page10.jpg", "Page2.jpg", "page1.jpg", "page02b.jpg", "page2a.jpg", "page"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, ["page", "page1.jpg", "Page2.jpg", "page2a.jpg", "page02b.jpg", "page10.jpg"]);
+        assert_eq!(natural_cmp("a001", "a1"), Ordering::Equal);
+        assert_eq!(natural_cmp("巻2", "巻10"), Ordering::Less);
+    }
+}
@@ -0,0 +1,210 @@
+//! Conversion of upstream mokuro output into the models used by this app.
+//! Two layouts of mokuro output are supported:
+//!   - The .mokuro file (mokuro >= 0.2), which contains the OCR of every page
+//!     alongside the path of each image relative to the .mokuro file.
+//!   - The older `_ocr/<volume>/<page>.json` layout, where the images are
+//!     located within the `<volume>/` directory.
+//!
+//! Archives of images without any OCR (i.e. a plain CBZ) are also supported.
+use std::collections::{HashMap, HashSet};
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use yew::AttrValue;
+
+use crate::errors::{AppError, InvalidMokuroFileError};
+use crate::models::{PageOcr, VolumeMetadata};
+use crate::utils::natural_cmp;
+use crate::utils::zip::PendingVolume;
+
+pub const MOKURO_EXTENSION: &str = ".mokuro";
+const IMAGE_EXTENSIONS: [&str; 7] = ["avif", "bmp", "gif", "jpeg", "jpg", "png", "webp"];
+const OCR_DIRECTORY: &str = "_ocr";
+
+/// The name of a volume alongside its (ocr entry, image entry) pairs.
+type LegacyLayout = (String, Vec<(String, String)>);
+
+#[derive(Deserialize)]
+struct MokuroFile {
+    #[serde(default)]
+    version: AttrValue,
+    #[serde(default)]
+    title: AttrValue,
+    #[serde(default)]
+    volume: AttrValue,
+    #[serde(default)]
+    volume_uuid: Option<AttrValue>,
+    pages: Vec<MokuroPage>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct MokuroPage {
+    img_path: String,
+    #[serde(flatten)]
+    ocr: PageOcr,
+}
+
+/// Whether the archive entry is an image, ignoring hidden files and macOS metadata.
+pub fn is_image(entry: &str) -> bool {
+    let name = basename(entry);
+    let extension = name.rsplit_once('.').map(|(_, ext)| ext.to_ascii_lowercase());
+    !name.starts_with('.')
+        && !entry.starts_with("__MACOSX/")
+        && extension.is_some_and(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+}
+
+/// Converts the contents of a .mokuro file into a volume.
+/// `base` is the directory of the .mokuro file within the archive
+/// (if applicable), which the image paths are relative to.
+pub fn from_mokuro_file(data: &[u8], base: &str, entries: &[String]) -> crate::Result<PendingVolume> {
+    let file: MokuroFile = serde_json::from_slice(data)?;
+    let mut pages = Vec::with_capacity(file.pages.len());
+    for page in file.pages.into_iter() {
+        let entry = resolve_image(base, &page.img_path, entries).ok_or_else(|| {
+            AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(page.img_path))
+        })?;
+        pages.push((entry, page.ocr));
+    }
+
+    let title = if file.volume.is_empty() { file.title.clone() } else { file.volume.clone() };
+    let mut volume =
+        VolumeMetadata::new(title, file.volume, file.volume_uuid, page_names(&pages));
+    volume.series = file.title;
+    volume.version = file.version;
+    volume.extra = file.extra;
+    Ok(PendingVolume { volume, pages })
+}
+
+/// Finds the OCR files of the `_ocr/<volume>/<page>.json` layout, returning the name
+/// of the volume and its (ocr entry, image entry) pairs in natural order.
+/// Returns None if the archive does not contain an `_ocr` directory.
+pub fn find_legacy_layout(entries: &[String]) -> crate::Result<Option<LegacyLayout>> {
+    // Images are matched to their OCR file by their path without the extension.
+    let images: HashMap<&str, &String> = entries.iter()
+        .filter(|entry| is_image(entry))
+        .filter_map(|entry| entry.rsplit_once('.').map(|(stem, _)| (stem, entry)))
+        .collect();
+
+    let mut volumes: Vec<String> = vec![];
+    let mut pages = vec![];
+    for entry in entries.iter() {
+        let Some(stem) = entry.strip_suffix(".json") else { continue; };
+        let components: Vec<&str> = stem.split('/').collect();
+        let Some(index) = components.iter().position(|c| *c == OCR_DIRECTORY) else { continue; };
+        if components.len() < index + 3 { continue; }
+
+        let volume = components[index + 1];
+        if !volumes.iter().any(|v| v == volume) {
+            volumes.push(volume.to_owned());
+        }
+        // <prefix>/_ocr/<volume>/<page>.json -> <prefix>/<volume>/<page>.<ext>
+        let image_stem = components[..index].iter()
+            .chain(components[index + 1..].iter())
+            .copied().collect::<Vec<&str>>().join("/");
+        let image = images.get(image_stem.as_str()).ok_or_else(|| {
+            AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(image_stem))
+        })?;
+        pages.push((entry.clone(), (*image).clone()));
+    }
+
+    match volumes.len() {
+        0 => Ok(None),
+        1 => {
+            pages.sort_by(|(a, _), (b, _)| natural_cmp(a, b));
+            Ok(Some((volumes.remove(0), pages)))
+        }
+        _ => Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::MultipleVolumes(volumes)))
+    }
+}
+
+/// Converts the pages of the `_ocr/<volume>/<page>.json` layout into a volume.
+pub fn from_legacy_layout(name: String, pages: Vec<(String, PageOcr)>) -> PendingVolume {
+    let title: AttrValue = name.into();
+    let mut volume = VolumeMetadata::new(title.clone(), title, None, page_names(&pages));
+    if let Some(version) = pages.first().and_then(|(_, ocr)| ocr.extra.get("version")) {
+        volume.version = version.as_str().unwrap_or_default().to_owned().into();
+    }
+    PendingVolume { volume, pages }
+}
+
+/// Converts an archive of images without any OCR into a volume, where the pages are
+/// the images in natural order. Returns None if the archive does not contain images.
+/// The dimensions of each page are left empty, as they require decoding the images.
+pub fn from_images(name: &str, entries: &[String]) -> Option<PendingVolume> {
+    let mut images: Vec<&String> = entries.iter().filter(|entry| is_image(entry)).collect();
+    if images.is_empty() { return None; }
+    images.sort_by(|a, b| natural_cmp(a, b));
+
+    let pages: Vec<(String, PageOcr)> =
+        images.into_iter().map(|entry| (entry.clone(), PageOcr::default())).collect();
+    let title: AttrValue = name.to_owned().into();
+    let volume = VolumeMetadata::new(title.clone(), title, None, page_names(&pages));
+    Some(PendingVolume { volume, pages })
+}
+
+/// Creates the (page_name, ocr_name) pairs for pages which were not named by the
+/// mokuro fork. The image file name is used as the page name, unless it is not unique,
+/// in which case the path of the entry is used, or failing that the path with a number.
+/// The OCR name keeps the extension of the image (e.g. `_ocr/001.jpg.json`), as images
+/// such as 001.jpg and 001.png would otherwise share the same OCR file.
+pub fn page_names<T>(pages: &[(String, T)]) -> Box<[(AttrValue, AttrValue)]> {
+    let mut seen = HashSet::with_capacity(pages.len());
+    pages.iter().map(|(entry, _)| {
+        let (stem, extension) = entry.rsplit_once('.').unwrap_or((entry, ""));
+        let numbered = (2..).map(|n| match extension {
+            "" => format!("{stem} ({n})"),
+            _ => format!("{stem} ({n}).{extension}"),
+        });
+        let name = [basename(entry).to_owned(), entry.clone()].into_iter().chain(numbered)
+            .find(|name| !seen.contains(name))
+            .unwrap_or_default();
+        seen.insert(name.clone());
+        let ocr_name = format!("{OCR_DIRECTORY}/{name}.json");
+        (AttrValue::from(name), AttrValue::from(ocr_name))
+    }).collect()
+}
+
+/// Resolves the path of an image within a .mokuro file to an entry of the archive.
+/// If the path does not match exactly, the entry which matches the most trailing
+/// components of the path is used, i.e. "volume/001.jpg" can match "001.jpg".
+fn resolve_image(base: &str, img_path: &str, entries: &[String]) -> Option<String> {
+    let img_path = img_path.replace('\\', "/");
+    let exact = if base.is_empty() { img_path.clone() } else { format!("{base}/{img_path}") };
+    if entries.contains(&exact) {
+        return Some(exact);
+    }
+
+    let components: Vec<&str> =
+        img_path.split('/').filter(|c| !c.is_empty() && *c != ".").collect();
+    (0..components.len()).find_map(|index| {
+        let suffix = components[index..].join("/");
+        let nested = format!("/{suffix}");
+        entries.iter().find(|entry| **entry == suffix || entry.ends_with(&nested)).cloned()
+    })
+}
+
+fn basename(entry: &str) -> &str {
+    entry.rsplit_once('/').map_or(entry, |(_, name)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_names_are_unique() {
+        let pages: Vec<(String, ())> = ["a/001.jpg", "a/001.png", "b/001.jpg", "001.jpg"].iter()
+            .map(|entry| (entry.to_string(), ()))
+            .collect();
+        let names = page_names(&pages);
+        let names: Vec<(&str, &str)> = names.iter().map(|(page, ocr)| (page.as_str(), ocr.as_str())).collect();
+        assert_eq!(names, [
+            ("001.jpg", "_ocr/001.jpg.json"),
+            ("001.png", "_ocr/001.png.json"),
+            ("b/001.jpg", "_ocr/b/001.jpg.json"),
+            ("001 (2).jpg", "_ocr/001 (2).jpg.json"),
+        ]);
+    }
+}
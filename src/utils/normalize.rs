@@ -0,0 +1,268 @@
+//! Normalization of the OCR text of a volume, i.e. making the mix of half-width and
+//! full-width characters, punctuation and whitespace which OCR produces consistent.
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use rexie::Rexie;
+use yew::AttrValue;
+
+use crate::models::{OcrBlock, PageOcr, VolumeId};
+use crate::utils::db::{get_ocr, put_ocr};
+
+/// The punctuation which is mapped, in order (so that `......` becomes `……`).
+const PUNCTUATION: [(&str, &str); 11] = [
+    ("...", "…"),
+    ("・・・", "…"),
+    ("。。。", "…"),
+    ("!!", "‼"),
+    ("！！", "‼"),
+    ("!?", "⁉"),
+    ("！？", "⁉"),
+    ("?!", "⁈"),
+    ("？！", "⁈"),
+    ("??", "⁇"),
+    ("？？", "⁇"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Normalization {
+    /// Half-width katakana (and punctuation) to full-width, e.g. ｶﾞ to ガ (a subset of NFKC).
+    HalfWidthKana,
+    /// Full-width letters and digits to ASCII, e.g. ＡＢ１ to AB1 (a subset of NFKC).
+    FullWidthAlphanumerics,
+    /// Runs of punctuation to their single character forms, e.g. `...` to `…` and `!!` to `‼`.
+    Punctuation,
+    /// Whitespace is trimmed from each line, and runs of whitespace are collapsed.
+    Whitespace,
+    /// Empty lines are removed, unless every line of the block is empty.
+    EmptyLines,
+}
+
+impl Normalization {
+    pub const ALL: [Self; 5] = [
+        Self::HalfWidthKana, Self::FullWidthAlphanumerics, Self::Punctuation, Self::Whitespace, Self::EmptyLines,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::HalfWidthKana => "Half-width katakana to full-width (ｶﾞ → ガ)",
+            Self::FullWidthAlphanumerics => "Full-width letters & digits to half-width (１２ → 12)",
+            Self::Punctuation => "Punctuation (... → …, !! → ‼, !? → ⁉)",
+            Self::Whitespace => "Trim & collapse whitespace",
+            Self::EmptyLines => "Remove empty lines",
+        }
+    }
+
+    /// Whether the normalization is selected by default. Full-width digits
+    /// are left alone by default, as they are preferred in vertical text.
+    pub fn default_enabled(&self) -> bool {
+        !matches!(self, Self::FullWidthAlphanumerics)
+    }
+
+    fn apply(&self, line: &str) -> String {
+        match self {
+            Self::HalfWidthKana => map_runs(line, |c| matches!(c, '\u{FF61}'..='\u{FF9F}'), |run| {
+                String::from(js_sys::JsString::from(run).normalize("NFKC"))
+            }),
+            Self::FullWidthAlphanumerics => line.chars().map(|c| match c {
+                '０'..='９' | 'Ａ'..='Ｚ' | 'ａ'..='ｚ' => char::from_u32(c as u32 - 0xFEE0).unwrap_or(c),
+                _ => c,
+            }).collect(),
+            Self::Punctuation => {
+                PUNCTUATION.iter().fold(line.to_owned(), |line, (from, to)| line.replace(from, to))
+            }
+            Self::Whitespace => map_runs(line.trim(), char::is_whitespace, |run| {
+                run.chars().take(1).collect()
+            }),
+            Self::EmptyLines => line.to_owned(),
+        }
+    }
+}
+
+/// A block which is changed by the normalizations.
+#[derive(Clone, PartialEq)]
+pub struct Change {
+    pub page: usize,
+    pub page_name: AttrValue,
+    /// The index of the block within the page.
+    pub index: usize,
+    pub before: OcrBlock,
+    pub after: OcrBlock,
+}
+
+/// The number of pages, blocks and lines changed by the normalizations,
+/// and the number of lines changed by each normalization.
+#[derive(Clone, Default, PartialEq)]
+pub struct Summary {
+    pub pages: usize,
+    pub blocks: usize,
+    pub lines: usize,
+    pub counts: Vec<(Normalization, usize)>,
+}
+
+/// Applies the normalizations (in order) to the lines of the block,
+/// returning the normalized block if it has changed.
+pub fn normalize_block(block: &OcrBlock, normalizations: &[Normalization]) -> Option<OcrBlock> {
+    let mut lines: Vec<String> = block.lines.iter()
+        .map(|line| normalizations.iter().fold(line.to_string(), |line, n| n.apply(&line)))
+        .collect();
+    if normalizations.contains(&Normalization::EmptyLines) && lines.iter().any(|line| !line.is_empty()) {
+        lines.retain(|line| !line.is_empty());
+    }
+    if lines.iter().map(String::as_str).eq(block.lines.iter().map(AttrValue::as_str)) {
+        return None;
+    }
+    let mut block = block.clone();
+    block.set_lines(lines.into_iter().map(AttrValue::from).collect());
+    Some(block)
+}
+
+/// The blocks of the pages which are changed by the normalizations.
+pub fn normalize_pages(pages: &[(usize, AttrValue, PageOcr)], normalizations: &[Normalization]) -> Vec<Change> {
+    let mut changes = vec![];
+    for (page, page_name, ocr) in pages {
+        for (index, block) in ocr.blocks.iter().enumerate() {
+            if let Some(after) = normalize_block(block, normalizations) {
+                let (page, page_name, before) = (*page, page_name.clone(), block.clone());
+                changes.push(Change { page, page_name, index, before, after });
+            }
+        }
+    }
+    changes
+}
+
+/// Summarizes the changes made by the normalizations (in order).
+pub fn summarize(changes: &[Change], normalizations: &[Normalization]) -> Summary {
+    let mut pages: Vec<usize> = changes.iter().map(|change| change.page).collect();
+    pages.dedup();
+    let lines = changes.iter().map(|change| {
+        let (before, after) = (&change.before.lines, &change.after.lines);
+        let changed = before.iter().zip(after.iter()).filter(|(a, b)| a != b).count();
+        changed + before.len().abs_diff(after.len())
+    }).sum();
+    // Each normalization is counted against the line as the normalizations before it left it,
+    // i.e. a line is counted once for each step of the pipeline which changes it.
+    let mut counts: Vec<(Normalization, usize)> = normalizations.iter().map(|n| (*n, 0)).collect();
+    for change in changes {
+        for line in change.before.lines.iter() {
+            let mut line = line.to_string();
+            for (n, count) in counts.iter_mut().filter(|(n, _)| *n != Normalization::EmptyLines) {
+                let normalized = n.apply(&line);
+                if normalized != line {
+                    *count += 1;
+                    line = normalized;
+                }
+            }
+        }
+        for (_, count) in counts.iter_mut().filter(|(n, _)| *n == Normalization::EmptyLines) {
+            *count += change.before.lines.len().saturating_sub(change.after.lines.len());
+        }
+    }
+    Summary { pages: pages.len(), blocks: changes.len(), lines, counts }
+}
+
+/// Writes the changes to the OCR of their pages. Blocks which have been
+/// edited since the changes were made are left alone.
+/// Returns the number of blocks which were changed.
+pub async fn apply_changes(db: &Rc<Rexie>, volume_id: VolumeId, changes: &[Change]) -> crate::Result<usize> {
+    let mut pages: BTreeMap<&AttrValue, Vec<&Change>> = BTreeMap::new();
+    for change in changes {
+        pages.entry(&change.page_name).or_default().push(change);
+    }
+    let mut count = 0;
+    for (page_name, changes) in pages {
+        let key = js_sys::Array::of2(&volume_id.into(), &page_name.as_str().into());
+        let mut ocr = get_ocr(db, &key).await?;
+        let mut changed = false;
+        for change in changes {
+            if let Some(block) = ocr.blocks.iter_mut().find(|block| **block == change.before) {
+                *block = change.after.clone();
+                changed = true;
+                count += 1;
+            }
+        }
+        if changed {
+            put_ocr(db, volume_id, page_name, &ocr).await?;
+        }
+    }
+    Ok(count)
+}
+
+/// Maps each run of characters which match the predicate.
+fn map_runs(text: &str, predicate: impl Fn(char) -> bool, map: impl Fn(&str) -> String) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find(&predicate) {
+        result.push_str(&rest[..start]);
+        let run = &rest[start..];
+        let end = run.find(|c| !predicate(c)).unwrap_or(run.len());
+        result.push_str(&map(&run[..end]));
+        rest = &run[end..];
+    }
+    result.push_str(rest);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Normalization::*;
+
+    // Half-width katakana are normalized by the browser (NFKC), so they are not tested here.
+    const NATIVE: [Normalization; 4] = [FullWidthAlphanumerics, Punctuation, Whitespace, EmptyLines];
+
+    fn block(lines: &[&str]) -> OcrBlock {
+        OcrBlock { lines: lines.iter().map(|line| line.to_string().into()).collect(), ..OcrBlock::default() }
+    }
+
+    fn normalize(lines: &[&str], normalizations: &[Normalization]) -> Option<Vec<String>> {
+        normalize_block(&block(lines), normalizations)
+            .map(|block| block.lines.iter().map(|line| line.to_string()).collect())
+    }
+
+    #[test]
+    fn normalizes_lines() {
+        assert_eq!(normalize(&["ＡＢ１２"], &NATIVE).unwrap(), ["AB12"]);
+        assert_eq!(normalize(&["え......!!", "？？！？"], &NATIVE).unwrap(), ["え……‼", "⁇⁉"]);
+        assert_eq!(normalize(&["  a \t b  "], &NATIVE).unwrap(), ["a b"]);
+        assert_eq!(normalize(&["a", " ", "b"], &NATIVE).unwrap(), ["a", "b"]);
+        assert_eq!(normalize(&["ｶﾞ"], &[Whitespace]), None);
+    }
+
+    #[test]
+    fn keeps_unchanged_and_empty_blocks() {
+        assert_eq!(normalize(&["あ…", "‼"], &NATIVE), None);
+        assert_eq!(normalize(&["", ""], &NATIVE), None);
+        assert_eq!(normalize(&["ＡＢ"], &[Punctuation]), None);
+    }
+
+    #[test]
+    fn discards_the_line_coordinates_of_removed_lines() {
+        let mut before = block(&["a", "", "b"]);
+        before.lines_coords = vec![vec![(0., 0.)]; 3];
+        assert!(normalize_block(&before, &NATIVE).unwrap().lines_coords.is_empty());
+    }
+
+    #[test]
+    fn summarizes_changes() {
+        let blocks = [block(&["ＡＢ...", "c  d"]), block(&["e", "", "f"]), block(&["g"])];
+        let pages: Vec<(usize, AttrValue, PageOcr)> = blocks.into_iter().enumerate().map(|(page, block)| {
+            (page, page.to_string().into(), PageOcr { blocks: vec![block], ..PageOcr::default() })
+        }).collect();
+        let changes = normalize_pages(&pages, &NATIVE);
+        assert_eq!(changes.iter().map(|change| (change.page, change.index)).collect::<Vec<_>>(), [(0, 0), (1, 0)]);
+
+        let Summary { pages, blocks, lines, counts } = summarize(&changes, &NATIVE);
+        assert_eq!((pages, blocks, lines), (2, 2, 4));
+        let counts: Vec<usize> = counts.into_iter().map(|(_, count)| count).collect();
+        assert_eq!(counts, [1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn maps_runs() {
+        let upper = |run: &str| run.to_uppercase();
+        assert_eq!(map_runs("ab1cd2e", |c| c.is_ascii_alphabetic(), upper), "AB1CD2E");
+        assert_eq!(map_runs("  a  b ", char::is_whitespace, |_| "_".into()), "_a_b_");
+        assert_eq!(map_runs("", char::is_whitespace, upper), "");
+    }
+}
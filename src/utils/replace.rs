@@ -0,0 +1,272 @@
+//! Find and replace within the OCR text of a volume.
+//!
+//! Patterns are JavaScript regular expressions (literal patterns are escaped), so the
+//! syntax is the same as the browser's. Replacements support `$&` (the match), `$1`-`$99`
+//! (capture groups) and `$$`, as `String.prototype.replace` does. Lines are searched one
+//! at a time, and matches are located by UTF-16 offsets, as JavaScript strings are.
+use std::ops::Range;
+
+use js_sys::{Array, RegExp, Reflect};
+use wasm_bindgen::{JsCast, JsValue};
+use yew::AttrValue;
+
+use crate::models::{OcrBlock, PageOcr};
+
+/// The number of characters displayed either side of a match in its preview.
+const CONTEXT: usize = 8;
+
+#[derive(Clone, Default, PartialEq)]
+pub struct FindOptions {
+    pub pattern: String,
+    pub replacement: String,
+    pub regex: bool,
+    pub match_case: bool,
+}
+
+/// An occurrence of the pattern within a line of a block.
+#[derive(Clone, PartialEq)]
+pub struct Match {
+    pub page: usize,
+    pub page_name: AttrValue,
+    pub block: AttrValue,
+    pub line: usize,
+    /// The UTF-16 range of the match within the line.
+    pub start: usize,
+    pub end: usize,
+    pub before: String,
+    pub text: String,
+    pub after: String,
+    pub replacement: String,
+}
+
+impl Match {
+    /// The match of the UTF-16 `range` of the line (`units`) of the block.
+    pub fn new(
+        page: usize, page_name: &AttrValue, block: &OcrBlock, line: usize,
+        units: &[u16], range: Range<usize>, replacement: String,
+    ) -> Self {
+        Self {
+            page,
+            page_name: page_name.clone(),
+            block: block.uuid.clone(),
+            line,
+            start: range.start,
+            end: range.end,
+            before: context_before(&units[..range.start]),
+            text: String::from_utf16_lossy(&units[range.clone()]),
+            after: context_after(&units[range.end..]),
+            replacement,
+        }
+    }
+}
+
+pub struct Finder {
+    regexp: RegExp,
+    replacement: String,
+}
+
+impl Finder {
+    /// Compiles the pattern, which fails if it is not a valid regular expression.
+    pub fn new(options: &FindOptions) -> crate::Result<Self> {
+        let (pattern, replacement) = if options.regex {
+            (options.pattern.clone(), options.replacement.clone())
+        } else {
+            (escape_pattern(&options.pattern), options.replacement.replace('$', "$$"))
+        };
+        let flags = if options.match_case { "gu" } else { "giu" };
+        // The RegExp constructor throws on invalid patterns, which js_sys does not catch.
+        let constructor = RegExp::new("", "").constructor();
+        let args = Array::of2(&pattern.into(), &flags.into());
+        let regexp = Reflect::construct(&constructor, &args)?.unchecked_into::<RegExp>();
+        Ok(Self { regexp, replacement })
+    }
+
+    /// Finds every (non-empty) match within the text of the blocks of the page.
+    pub fn find_page(&self, page: usize, page_name: &AttrValue, ocr: &PageOcr) -> Vec<Match> {
+        let mut matches = vec![];
+        for block in ocr.blocks.iter() {
+            for (index, line) in block.lines.iter().enumerate() {
+                let units: Vec<u16> = line.encode_utf16().collect();
+                for (start, end, replacement) in self.find_line(line) {
+                    matches.push(Match::new(page, page_name, block, index, &units, start..end, replacement));
+                }
+            }
+        }
+        matches
+    }
+
+    /// The UTF-16 range and the replacement of every (non-empty) match within the line.
+    fn find_line(&self, line: &str) -> Vec<(usize, usize, String)> {
+        let mut result = vec![];
+        self.regexp.set_last_index(0);
+        while let Some(found) = self.regexp.exec(line) {
+            // Groups which did not participate in the match are undefined, i.e. None.
+            let groups: Vec<Option<String>> = (0..found.length())
+                .map(|index| found.get(index).as_string())
+                .collect();
+            let matched = groups.first().cloned().flatten().unwrap_or_default();
+            let length = matched.encode_utf16().count();
+            if length == 0 {
+                // Empty matches are skipped, and the search moves past them.
+                self.regexp.set_last_index(self.regexp.last_index() + 1);
+                continue;
+            }
+            let start = Reflect::get(&found, &JsValue::from_str("index"))
+                .ok().and_then(|index| index.as_f64()).unwrap_or_default() as usize;
+            result.push((start, start + length, expand(&self.replacement, &groups)));
+        }
+        result
+    }
+}
+
+/// Applies the replacements of the matches (which must all belong to the block) to its lines.
+/// Returns None if none of the matches could be applied, i.e. the block has since changed.
+pub fn replace_block(block: &OcrBlock, matches: &[&Match]) -> Option<OcrBlock> {
+    let mut lines: Vec<Vec<u16>> = block.lines.iter().map(|line| line.encode_utf16().collect()).collect();
+    let mut sorted = matches.to_vec();
+    // Replacing from the end of each line keeps the offsets of the earlier matches valid.
+    sorted.sort_by_key(|m| std::cmp::Reverse((m.line, m.start)));
+    let mut applied = false;
+    let mut previous: Option<(usize, usize)> = None;
+    for m in sorted {
+        let Some(line) = lines.get_mut(m.line) else { continue; };
+        let overlaps = previous.is_some_and(|(line, start)| line == m.line && m.end > start);
+        if overlaps || m.end > line.len() || String::from_utf16_lossy(&line[m.start..m.end]) != m.text {
+            continue;
+        }
+        line.splice(m.start..m.end, m.replacement.encode_utf16());
+        previous = Some((m.line, m.start));
+        applied = true;
+    }
+    if !applied { return None; }
+    let mut block = block.clone();
+    block.set_lines(lines.iter().map(|line| String::from_utf16_lossy(line).into()).collect());
+    Some(block)
+}
+
+fn escape_pattern(pattern: &str) -> String {
+    let mut escaped = String::with_capacity(pattern.len());
+    for c in pattern.chars() {
+        if "\\^$.*+?()[]{}|/".contains(c) { escaped.push('\\'); }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Expands the `$` substitutions of the replacement with the groups of the match,
+/// where the first group is the entire match.
+fn expand(replacement: &str, groups: &[Option<String>]) -> String {
+    let group = |index: usize| -> Option<String> {
+        groups.get(index).map(|group| group.clone().unwrap_or_default())
+    };
+    let mut result = String::with_capacity(replacement.len());
+    let mut chars = replacement.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '$' {
+            result.push(c);
+            continue;
+        }
+        match chars.peek().copied() {
+            Some('$') => { chars.next(); result.push('$'); }
+            Some('&') => { chars.next(); result.push_str(&group(0).unwrap_or_default()); }
+            Some(d) if d.is_ascii_digit() => {
+                chars.next();
+                let mut index = d.to_digit(10).unwrap_or_default() as usize;
+                // Two digit groups are only used if the group exists, as in JavaScript.
+                if let Some(e) = chars.peek().and_then(|e| e.to_digit(10)) {
+                    let two_digits = index * 10 + e as usize;
+                    if group(two_digits).is_some() && two_digits > 0 {
+                        chars.next();
+                        index = two_digits;
+                    }
+                }
+                match group(index).filter(|_| index > 0) {
+                    Some(text) => result.push_str(&text),
+                    None => { result.push('$'); result.push(d); }
+                }
+            }
+            _ => result.push('$'),
+        }
+    }
+    result
+}
+
+fn context_before(units: &[u16]) -> String {
+    let text = String::from_utf16_lossy(units);
+    let count = text.chars().count();
+    if count <= CONTEXT { return text; }
+    format!("…{}", text.chars().skip(count - CONTEXT).collect::<String>())
+}
+
+fn context_after(units: &[u16]) -> String {
+    let text = String::from_utf16_lossy(units);
+    if text.chars().count() <= CONTEXT { return text; }
+    format!("{}…", text.chars().take(CONTEXT).collect::<String>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn groups(groups: &[Option<&str>]) -> Vec<Option<String>> {
+        groups.iter().map(|group| group.map(String::from)).collect()
+    }
+
+    #[test]
+    fn expands_substitutions() {
+        let groups = groups(&[Some("abc"), Some("a"), None]);
+        assert_eq!(expand("[$&]", &groups), "[abc]");
+        assert_eq!(expand("$1-$2-$3", &groups), "a--$3");
+        assert_eq!(expand("$$1 $0 $", &groups), "$1 $0 $");
+        assert_eq!(expand("$01$10", &groups), "aa0");
+    }
+
+    #[test]
+    fn expands_two_digit_groups() {
+        let mut many = vec![Some("whole")];
+        many.extend(["1", "2", "3", "4", "5", "6", "7", "8", "9", "10", "11"].map(Some));
+        assert_eq!(expand("$11 $12", &groups(&many)), "11 12");
+    }
+
+    #[test]
+    fn escapes_patterns() {
+        assert_eq!(escape_pattern("a.b*c"), "a\\.b\\*c");
+        assert_eq!(escape_pattern("(?)[x]{1}|/^$\\"), "\\(\\?\\)\\[x\\]\\{1\\}\\|\\/\\^\\$\\\\");
+        assert_eq!(escape_pattern("力ー"), "力ー");
+    }
+
+    fn matches(block: &OcrBlock, found: &[(usize, Range<usize>, &str)]) -> Vec<Match> {
+        found.iter().map(|(line, range, replacement)| {
+            let units: Vec<u16> = block.lines[*line].encode_utf16().collect();
+            Match::new(0, &"1".into(), block, *line, &units, range.clone(), replacement.to_string())
+        }).collect()
+    }
+
+    #[test]
+    fn replaces_matches_from_the_end_of_each_line() {
+        let lines = vec!["カ力カ力".into(), "𠮷野家".into()];
+        let block = OcrBlock { lines, ..OcrBlock::default() };
+        // 𠮷 is two UTF-16 units.
+        let found = matches(&block, &[(0, 1..2, "カ"), (0, 3..4, "カ"), (1, 0..2, "吉")]);
+        let replaced = replace_block(&block, &found.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(replaced.lines, ["カカカカ", "吉野家"]);
+    }
+
+    #[test]
+    fn skips_stale_and_overlapping_matches() {
+        let block = OcrBlock { lines: vec!["abcd".into()], ..OcrBlock::default() };
+        let found = matches(&block, &[(0, 0..2, "x"), (0, 1..3, "y")]);
+        let edited = OcrBlock { lines: vec!["zzzz".into()], ..OcrBlock::default() };
+        assert!(replace_block(&edited, &found.iter().collect::<Vec<_>>()).is_none());
+        let replaced = replace_block(&block, &found.iter().collect::<Vec<_>>()).unwrap();
+        assert_eq!(replaced.lines, ["ayd"]);
+    }
+
+    #[test]
+    fn previews_the_context_of_a_match() {
+        let block = OcrBlock { lines: vec!["0123456789X0123456789".into()], ..OcrBlock::default() };
+        let found = matches(&block, &[(0, 10..11, "")]);
+        assert_eq!((found[0].before.as_str(), found[0].text.as_str(), found[0].after.as_str()),
+                   ("…23456789", "X", "01234567…"));
+    }
+}
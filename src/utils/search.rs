@@ -0,0 +1,261 @@
+//! Full-text search of the OCR text of the library.
+//!
+//! The text of a block is its lines joined together (mokuro splits the text of
+//! a block into lines, which do not contain spaces for Japanese text), so that
+//! a search matches text which spans multiple lines of a block.
+//!
+//! As Japanese text has no spaces between words, the text is indexed by its character
+//! bigrams and trigrams (the `search` store, see `db::create_database`). The pages which
+//! contain every n-gram of the query are candidates, which are then checked against
+//! their OCR, as the n-grams may be in a different order or in different blocks.
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::rc::Rc;
+
+use rexie::Rexie;
+use serde::{Deserialize, Serialize};
+use yew::AttrValue;
+
+use crate::models::{OcrBlock, PageOcr, VolumeId, VolumeMetadata};
+use crate::utils::db::{get_all_volumes, get_ocr, get_search_candidates, get_volume_ocr};
+use crate::utils::natural_cmp;
+
+/// The maximum number of pages returned by a search.
+const MAX_PAGES: usize = 200;
+
+/// Which volumes of the library are searched.
+#[derive(Clone, Default, PartialEq)]
+pub enum SearchScope {
+    #[default]
+    Library,
+    Series(AttrValue),
+    Volume(VolumeId),
+}
+
+impl SearchScope {
+    /// Parses the string form of a scope, i.e. "", "series:<name>" or "volume:<id>".
+    /// Unrecognised scopes fall back to the entire library.
+    pub fn parse(value: &str) -> Self {
+        if let Some(series) = value.strip_prefix("series:") {
+            return Self::Series(series.to_owned().into());
+        }
+        match value.strip_prefix("volume:").map(str::parse) {
+            Some(Ok(volume_id)) => Self::Volume(volume_id),
+            _ => Self::Library,
+        }
+    }
+
+    pub fn contains(&self, volume: &VolumeMetadata) -> bool {
+        match self {
+            Self::Library => true,
+            Self::Series(series) => &volume.series == series,
+            Self::Volume(volume_id) => volume.id == *volume_id,
+        }
+    }
+}
+
+impl Display for SearchScope {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Library => Ok(()),
+            Self::Series(series) => write!(f, "series:{series}"),
+            Self::Volume(volume_id) => write!(f, "volume:{volume_id}"),
+        }
+    }
+}
+
+/// A page containing at least one block which matched the search.
+#[derive(Clone, PartialEq)]
+pub struct PageResult {
+    pub volume_id: VolumeId,
+    pub volume_title: AttrValue,
+    pub page: usize,
+    pub page_name: AttrValue,
+    pub blocks: Vec<BlockMatch>,
+}
+
+/// A block which matched the search, with the (byte) ranges of each match within its text.
+#[derive(Clone, PartialEq)]
+pub struct BlockMatch {
+    pub uuid: AttrValue,
+    pub text: AttrValue,
+    pub matches: Vec<Range<usize>>,
+}
+
+#[derive(Clone, Default, PartialEq)]
+pub struct SearchResults {
+    pub pages: Vec<PageResult>,
+    /// Whether the search stopped early after finding MAX_PAGES pages.
+    pub truncated: bool,
+}
+
+/// The row of the `search` store for a page, keyed the same as its OCR.
+#[derive(Serialize, Deserialize)]
+pub struct SearchEntry {
+    pub volume_id: VolumeId,
+    pub page: AttrValue,
+    /// The (sorted) bigrams and trigrams of the text of every block of the page.
+    pub grams: Vec<String>,
+}
+
+impl SearchEntry {
+    pub fn new(volume_id: VolumeId, page: &AttrValue, ocr: &PageOcr) -> Self {
+        let mut grams = BTreeSet::new();
+        for block in ocr.blocks.iter() {
+            let text = normalize(&block_text(block));
+            for n in [2, 3] {
+                grams.extend(text.windows(n).map(String::from_iter));
+            }
+        }
+        Self { volume_id, page: page.clone(), grams: grams.into_iter().collect() }
+    }
+
+    /// Whether the page contains every one of the (sorted) grams.
+    pub fn contains(&self, grams: &[String]) -> bool {
+        grams.iter().all(|gram| self.grams.binary_search(gram).is_ok())
+    }
+}
+
+/// Searches the text of every block of the volumes within the scope, ignoring case.
+/// Volumes are searched in order of series and then title.
+pub async fn search(db: &Rc<Rexie>, query: &str, scope: &SearchScope) -> crate::Result<SearchResults> {
+    let query = query.trim();
+    let mut results = SearchResults::default();
+    if query.is_empty() { return Ok(results); }
+
+    let mut volumes: Vec<_> = get_all_volumes(db.clone()).await?
+        .into_iter().filter(|volume| scope.contains(volume)).collect();
+    volumes.sort_by(|a, b| natural_cmp(&a.series, &b.series).then_with(|| natural_cmp(&a.title, &b.title)));
+
+    // Single characters are not indexed, so the text of every page is searched.
+    let grams = query_grams(query);
+    let candidates = if grams.is_empty() { None } else {
+        let mut candidates: BTreeMap<VolumeId, BTreeSet<AttrValue>> = BTreeMap::new();
+        for entry in get_search_candidates(db, &grams).await? {
+            candidates.entry(entry.volume_id).or_default().insert(entry.page);
+        }
+        Some(candidates)
+    };
+
+    for volume in volumes.iter() {
+        let pages: Vec<(usize, &AttrValue, PageOcr)> = match &candidates {
+            None => {
+                let ocr = get_volume_ocr(db, volume).await?;
+                volume.pages.iter().zip(ocr).enumerate()
+                    .map(|(page, ((page_name, _), ocr))| (page, page_name, ocr))
+                    .collect()
+            }
+            Some(candidates) => {
+                let Some(names) = candidates.get(&volume.id) else { continue; };
+                let mut pages = vec![];
+                for (page, (page_name, _)) in volume.pages.iter().enumerate() {
+                    if !names.contains(page_name) { continue; }
+                    let key = js_sys::Array::of2(&volume.id.into(), &page_name.as_str().into());
+                    pages.push((page, page_name, get_ocr(db, &key).await?));
+                }
+                pages
+            }
+        };
+        for (page, page_name, ocr) in pages {
+            let blocks: Vec<_> = ocr.blocks.iter().filter_map(|block| {
+                let text = block_text(block);
+                let matches = find_all_ignore_case(&text, query);
+                (!matches.is_empty()).then(|| BlockMatch { uuid: block.uuid.clone(), text: text.into(), matches })
+            }).collect();
+            if blocks.is_empty() { continue; }
+            if results.pages.len() == MAX_PAGES {
+                results.truncated = true;
+                return Ok(results);
+            }
+            results.pages.push(PageResult {
+                volume_id: volume.id,
+                volume_title: volume.title.clone(),
+                page,
+                page_name: page_name.clone(),
+                blocks,
+            });
+        }
+    }
+    Ok(results)
+}
+
+fn block_text(block: &OcrBlock) -> String {
+    block.lines.concat()
+}
+
+/// Lowercases the text, so that the n-grams are case-insensitive.
+fn normalize(text: &str) -> Vec<char> {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
+
+/// The (sorted) n-grams which every page containing the query must contain:
+/// the trigrams of the query, or its bigram if it is only two characters long.
+pub fn query_grams(query: &str) -> Vec<String> {
+    let query = normalize(query);
+    let n = query.len().min(3);
+    if n < 2 { return vec![]; }
+    let grams: BTreeSet<String> = query.windows(n).map(String::from_iter).collect();
+    grams.into_iter().collect()
+}
+
+/// Finds the (non-overlapping) byte ranges of every occurrence of `pattern` within `text`,
+/// comparing characters case-insensitively.
+fn find_all_ignore_case(text: &str, pattern: &str) -> Vec<Range<usize>> {
+    let mut matches = vec![];
+    let mut start = 0;
+    while start < text.len() {
+        match match_at(&text[start..], pattern) {
+            Some(length) => {
+                matches.push(start..start + length);
+                start += length;
+            }
+            None => start += text[start..].chars().next().map_or(1, char::len_utf8),
+        }
+    }
+    matches
+}
+
+/// The byte length of the match if `text` starts with `pattern` (ignoring case).
+fn match_at(text: &str, pattern: &str) -> Option<usize> {
+    let mut chars = text.char_indices();
+    for expected in pattern.chars() {
+        let (_, actual) = chars.next()?;
+        if !actual.to_lowercase().eq(expected.to_lowercase()) { return None; }
+    }
+    Some(chars.next().map_or(text.len(), |(index, _)| index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn query_grams_are_the_trigrams_or_bigram() {
+        assert!(query_grams("あ").is_empty());
+        assert_eq!(query_grams("AB"), ["ab"]);
+        assert_eq!(query_grams("ありがとう"), ["ありが", "がとう", "りがと"]);
+        assert_eq!(query_grams("ああああ"), ["あああ"]);
+    }
+
+    #[test]
+    fn finds_every_occurrence_ignoring_case() {
+        assert_eq!(find_all_ignore_case("Abc abc ABC", "abc"), [0..3, 4..7, 8..11]);
+        assert_eq!(find_all_ignore_case("ああああ", "ああ"), [0..6, 6..12]);
+        assert_eq!(find_all_ignore_case("一二三二", "二"), [3..6, 9..12]);
+        assert!(find_all_ignore_case("ab", "abc").is_empty());
+    }
+
+    #[test]
+    fn entries_contain_the_grams_of_every_block() {
+        let blocks = [["ありが", "とう"], ["ABC", "d"]].iter().map(|lines| OcrBlock {
+            lines: lines.iter().map(|line| line.to_string().into()).collect(),
+            ..OcrBlock::default()
+        }).collect();
+        let entry = SearchEntry::new(1, &"1".into(), &PageOcr { blocks, ..PageOcr::default() });
+        // The lines of a block are joined, but blocks are not.
+        assert!(entry.contains(&query_grams("がとう")));
+        assert!(entry.contains(&query_grams("abcd")));
+        assert!(!entry.contains(&query_grams("うab")));
+    }
+}
@@ -0,0 +1,56 @@
+//! Small images of the volume covers, which are displayed in the library
+//! instead of the full resolution covers, and of the pages in the search results.
+use std::rc::Rc;
+
+use gloo_console as console;
+use rexie::Rexie;
+use wasm_bindgen::JsValue;
+use yew::AttrValue;
+
+use crate::models::{PageImage, VolumeId};
+use crate::utils::db::{get_page, get_thumbnail, put_thumbnail};
+use crate::utils::web::resize_image;
+
+/// Twice the width of the covers in the library, so they remain sharp on high-DPI screens.
+const THUMBNAIL_WIDTH: u32 = 512;
+/// Twice the width of the pages in the search results.
+const PAGE_THUMBNAIL_WIDTH: u32 = 340;
+
+/// Creates the thumbnail of the cover and writes it into the mokuro IndexedDB.
+pub async fn write_thumbnail(db: &Rc<Rexie>, volume_id: VolumeId, cover: &PageImage) -> crate::Result<PageImage> {
+    let blob: &gloo_file::Blob = cover.as_ref();
+    let thumbnail: PageImage = JsValue::from(resize_image(blob.as_ref(), THUMBNAIL_WIDTH).await?).into();
+    put_thumbnail(db, volume_id, &thumbnail).await?;
+    Ok(thumbnail)
+}
+
+/// Retrieves the thumbnail of the volume, creating it from the cover if it does not exist yet
+/// (i.e. the volume was imported before thumbnails existed, or creating it failed on import).
+/// Falls back to the full resolution cover if the thumbnail cannot be created.
+pub async fn load_thumbnail(db: &Rc<Rexie>, volume_id: VolumeId, cover: AttrValue) -> crate::Result<PageImage> {
+    if let Some(thumbnail) = get_thumbnail(db, volume_id).await? {
+        return Ok(thumbnail);
+    }
+    let cover = get_page(db.clone(), volume_id, cover).await?;
+    match write_thumbnail(db, volume_id, &cover).await {
+        Ok(thumbnail) => Ok(thumbnail),
+        Err(error) => {
+            console::warn!(format!("failed to create thumbnail: {}", error));
+            Ok(cover)
+        }
+    }
+}
+
+/// Creates a small image of the page. Unlike the thumbnails of the covers, these are
+/// not stored. Falls back to the full resolution page if it cannot be resized.
+pub async fn load_page_thumbnail(db: &Rc<Rexie>, volume_id: VolumeId, page: AttrValue) -> crate::Result<PageImage> {
+    let page = get_page(db.clone(), volume_id, page).await?;
+    let blob: &gloo_file::Blob = page.as_ref();
+    match resize_image(blob.as_ref(), PAGE_THUMBNAIL_WIDTH).await {
+        Ok(thumbnail) => Ok(JsValue::from(thumbnail).into()),
+        Err(error) => {
+            console::warn!(format!("failed to create page thumbnail: {:?}", error));
+            Ok(page)
+        }
+    }
+}
@@ -32,6 +32,48 @@ pub async fn ask_to_persist_storage() -> Result<bool, wasm_bindgen::JsValue> {
     Ok(result.as_bool().unwrap_or(false))
 }
 
+/// The (usage, quota) estimate of the storage available to this site, in bytes.
+/// ref: developer.mozilla.org/docs/Web/API/StorageManager/estimate
+pub async fn get_storage_estimate() -> Result<(f64, f64), wasm_bindgen::JsValue> {
+    let promise = window().navigator().storage().estimate()?;
+    let estimate = wasm_bindgen_futures::JsFuture::from(promise).await?;
+    let get = |key: &str| js_sys::Reflect::get(&estimate, &key.into()).map(|v| v.as_f64().unwrap_or_default());
+    Ok((get("usage")?, get("quota")?))
+}
+
+/// Decodes the image to determine its (width, height).
+pub async fn get_image_dimensions(blob: &web_sys::Blob) -> Result<(u32, u32), wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+    let promise = window().create_image_bitmap_with_blob(blob)?;
+    let bitmap: web_sys::ImageBitmap = wasm_bindgen_futures::JsFuture::from(promise).await?.unchecked_into();
+    let dimensions = (bitmap.width(), bitmap.height());
+    bitmap.close();
+    Ok(dimensions)
+}
+
+/// Scales the image down to the given width (keeping its aspect ratio) and encodes it as a JPEG.
+pub async fn resize_image(blob: &web_sys::Blob, width: u32) -> Result<web_sys::Blob, wasm_bindgen::JsValue> {
+    use wasm_bindgen::JsCast;
+    let options = web_sys::ImageBitmapOptions::new();
+    options.set_resize_width(width);
+    options.set_resize_quality(web_sys::ResizeQuality::High);
+    let promise = window().create_image_bitmap_with_blob_and_image_bitmap_options(blob, &options)?;
+    let bitmap: web_sys::ImageBitmap = wasm_bindgen_futures::JsFuture::from(promise).await?.unchecked_into();
+
+    let canvas = web_sys::OffscreenCanvas::new(bitmap.width(), bitmap.height())?;
+    let context: web_sys::OffscreenCanvasRenderingContext2d = canvas.get_context("2d")?
+        .ok_or("failed to create canvas context")?
+        .unchecked_into();
+    context.draw_image_with_image_bitmap(&bitmap, 0.0, 0.0)?;
+    bitmap.close();
+
+    let encoding = web_sys::ImageEncodeOptions::new();
+    encoding.set_type("image/jpeg");
+    encoding.set_quality(0.8);
+    let promise = canvas.convert_to_blob_with_options(&encoding)?;
+    Ok(wasm_bindgen_futures::JsFuture::from(promise).await?.unchecked_into())
+}
+
 pub fn get_screen_size() -> (f64, f64) {
     let window = window();
     let width = window.inner_width().unwrap().as_f64().unwrap();
@@ -91,6 +133,14 @@ pub fn get_input_bool(node: &yew::NodeRef) -> Option<bool> {
     node.cast::<web_sys::HtmlInputElement>().map(|elm| elm.checked())
 }
 
+pub fn get_input_value(node: &yew::NodeRef) -> Option<String> {
+    node.cast::<web_sys::HtmlInputElement>().map(|elm| elm.value())
+}
+
+pub fn get_select_value(node: &yew::NodeRef) -> Option<String> {
+    node.cast::<web_sys::HtmlSelectElement>().map(|elm| elm.value())
+}
+
 pub fn get_input_f64(node: &yew::NodeRef) -> Option<f64> {
     node.cast::<web_sys::HtmlInputElement>()
         .and_then(|elm| elm.check_validity().then_some(elm.value_as_number()))
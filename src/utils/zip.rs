@@ -1,133 +1,342 @@
-use std::io::{Cursor, Read, Seek, Write};
+use std::cell::Cell;
 use std::rc::Rc;
 
+use gloo_console as console;
 use gloo_file::futures::read_as_bytes as gloo_file_read;
 use rexie::Rexie;
-use zip::{read::ZipArchive, result::ZipError, write::{SimpleFileOptions, ZipWriter}};
+use serde::de::DeserializeOwned;
+use yew::Callback;
 
+use crate::errors::{AppError, InvalidMokuroFileError};
 use crate::models::{PageImage, PageOcr, VolumeId, VolumeMetadata};
-use crate::utils::db::{get_page_and_ocr, get_settings, get_volume, put_volume, start_bulk_write_txn};
+use crate::utils::blob_zip::{BlobZipArchive, BlobZipWriter};
+use crate::utils::db::{
+    delete_volume, get_page, get_page_and_ocr, get_settings, get_volume, get_volume_ocr,
+    get_volume_snapshots, put_volume, put_volume_and_ocr, search_entry, serde_to_wasm, start_bulk_write_txn,
+};
+use crate::utils::{iso_timestamp, timestamp};
+use crate::utils::merge::merge_page;
+use crate::utils::mokuro::{self, MOKURO_EXTENSION};
+use crate::utils::thumbnail::write_thumbnail;
+use crate::utils::web::get_image_dimensions;
 
-const METADATA_FILE: &str = "mokuro-metadata.json";
+pub const METADATA_FILE: &str = "mokuro-metadata.json";
 
-/// extract a zip archive in memory and inserts the data into the mokuro IndexedDB.
+/// A volume which has been read from an archive, but not yet written to IndexedDB.
+/// Each page is the name of its image within the archive alongside its OCR data,
+/// in the same order as `volume.pages`.
+pub struct PendingVolume {
+    pub volume: VolumeMetadata,
+    pub pages: Vec<(String, PageOcr)>,
+}
+
+/// An archive which has been read and validated, but not yet written to IndexedDB.
+pub struct PreparedImport {
+    archive: BlobZipArchive,
+    pending: PendingVolume,
+}
+
+impl PreparedImport {
+    pub fn volume(&self) -> &VolumeMetadata {
+        &self.pending.volume
+    }
+}
+
+/// How an import is written when the library already contains the volume
+/// (i.e. a volume with the same `volume_uuid`).
+pub enum ImportMode {
+    /// The volume is added alongside any existing copies.
+    New,
+    /// The existing volume is deleted once the import has succeeded.
+    Replace(VolumeMetadata),
+    /// As Replace, but the reading progress and settings of the existing volume are kept.
+    Merge(VolumeMetadata),
+    /// The textbox edits of the upload are merged into the existing volume, keeping its
+    /// pages. Textboxes which were edited in both are recorded as conflicts.
+    MergeEdits(VolumeMetadata),
+}
+
+/// read and validate a zip archive, without writing anything to IndexedDB.
+/// The archive may be a volume created by the mokuro fork (*.mbz.zip), or the
+/// output of upstream mokuro (a .mokuro file or _ocr directory alongside the images).
+/// If `mokuro_file` is provided, the archive is expected to only contain the images.
+/// The archive is read incrementally, so only a single page is held in memory at a time.
+pub async fn prepare_ziparchive(
+    file_obj: gloo_file::File, mokuro_file: Option<gloo_file::File>,
+) -> crate::Result<PreparedImport> {
+    let filename = file_obj.name();
+    let archive = BlobZipArchive::new(file_obj.into()).await?;
+    let pending = match mokuro_file {
+        Some(mokuro_file) => {
+            let data = gloo_file_read(&mokuro_file).await?;
+            let entries = archive_entries(&archive);
+            mokuro::from_mokuro_file(&data, "", &entries)
+                .map_err(entry_error(&mokuro_file.name()))?
+        }
+        None => read_volume(&archive, &filename).await?,
+    };
+    validate(&archive, &pending)?;
+    Ok(PreparedImport { archive, pending })
+}
+
+/// extract a prepared zip archive and inserts the data into the mokuro IndexedDB.
 pub async fn extract_ziparchive(
-    db: &Rc<Rexie>, file_obj: gloo_file::File,
+    db: &Rc<Rexie>, prepared: PreparedImport, mode: ImportMode,
 ) -> crate::Result<(VolumeMetadata, gloo_file::ObjectUrl)> {
+    let PreparedImport { archive, mut pending } = prepared;
     let global_settings = get_settings(db).await?;
-    let mut archive = {
-        let reader = Cursor::new(gloo_file_read(&file_obj).await?);
-        ZipArchive::new(reader)?
+    pending.volume.magnifier = global_settings.magnifier;
+    let existing = match mode {
+        ImportMode::New => None,
+        ImportMode::Replace(existing) => Some(existing),
+        ImportMode::Merge(existing) => {
+            pending.volume.keep_progress(&existing);
+            Some(existing)
+        }
+        ImportMode::MergeEdits(existing) => return merge_edits(db, existing, pending).await,
     };
 
-    let volume = {
-        let mut volume = {
-            let data = read_zipfile(&mut archive, METADATA_FILE)?;
-            serde_json::from_slice::<VolumeMetadata>(&data)?
-        };
-        volume.id = 0;  // ensure id is not specified. IndexDB determines this.
-        volume.magnifier = global_settings.magnifier;
-        volume.id = put_volume(db, &volume).await?;
-        volume
-    };
+    let result = write_volume(db, &archive, pending).await?;
+    if let Some(existing) = existing {
+        if let Err(error) = delete_volume(db, existing.id).await {
+            console::warn!(format!("failed to delete the replaced volume: {}", error));
+        }
+    }
+    Ok(result)
+}
+
+/// Determine the format of the archive and read the volume metadata and OCR data.
+/// An archive of images without any OCR is named after the archive file.
+async fn read_volume(archive: &BlobZipArchive, filename: &str) -> crate::Result<PendingVolume> {
+    let entries = archive_entries(archive);
+    if entries.iter().any(|entry| entry == METADATA_FILE) {
+        let volume: VolumeMetadata = read_json(archive, METADATA_FILE).await?;
+        let mut pages = Vec::with_capacity(volume.pages.len());
+        for (page_name, ocr_name) in volume.pages.iter() {
+            pages.push((page_name.to_string(), read_json(archive, ocr_name).await?));
+        }
+        return Ok(PendingVolume { volume, pages });
+    }
 
-    let cover = volume.cover();
-    let cover_object_url = {
-        let cover_data = read_zipfile(&mut archive, cover)?;
-        PageImage::new(cover, &cover_data[..]).into()
+    let mokuro_files: Vec<&String> = entries.iter()
+        .filter(|entry| entry.ends_with(MOKURO_EXTENSION) && !entry.starts_with("__MACOSX/"))
+        .collect();
+    match mokuro_files[..] {
+        [] => {}
+        [name] => {
+            let data = archive.read(name).await.map_err(entry_error(name))?;
+            let base = name.rsplit_once('/').map_or("", |(base, _)| base);
+            return mokuro::from_mokuro_file(&data, base, &entries).map_err(entry_error(name));
+        }
+        _ => {
+            let names = mokuro_files.into_iter().cloned().collect();
+            return Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::MultipleVolumes(names)));
+        }
+    }
+
+    let Some((name, files)) = mokuro::find_legacy_layout(&entries)? else {
+        let name = [".cbz", ".zip"].iter()
+            .find_map(|ext| filename.strip_suffix(ext))
+            .unwrap_or(filename);
+        return mokuro::from_images(name, &entries)
+            .ok_or(AppError::InvalidMokuroFile(InvalidMokuroFileError::UnknownFormat));
     };
+    let mut pages = Vec::with_capacity(files.len());
+    for (ocr_entry, image_entry) in files.into_iter() {
+        pages.push((image_entry, read_json(archive, &ocr_entry).await?));
+    }
+    Ok(mokuro::from_legacy_layout(name, pages))
+}
+
+/// Merges the OCR of the pending volume into the existing volume, page by page.
+/// Pages which are not in the existing volume are ignored.
+async fn merge_edits(
+    db: &Rc<Rexie>, mut volume: VolumeMetadata, pending: PendingVolume,
+) -> crate::Result<(VolumeMetadata, gloo_file::ObjectUrl)> {
+    let ours = get_volume_ocr(db, &volume).await?;
+    let base = get_volume_snapshots(db, &volume).await?;
+    let mut merged = vec![];
+    for (index, (page_name, _)) in volume.pages.iter().enumerate() {
+        let Some(position) = pending.volume.page_index(page_name) else { continue; };
+        let theirs = &pending.pages[position].1;
+        let (page_ocr, conflicts) = merge_page(index, base[index].as_ref(), &ours[index], theirs);
+        // A new conflict supersedes any unresolved conflict of the same textbox.
+        volume.conflicts.retain(|c| c.page != index || !conflicts.iter().any(|n| n.uuid() == c.uuid()));
+        volume.conflicts.extend(conflicts);
+        merged.push((page_name.clone(), page_ocr));
+    }
+    volume.modified_at = iso_timestamp().into();
+    put_volume_and_ocr(db, &volume, &merged).await?;
 
+    let cover = get_page(db.clone(), volume.id, volume.cover().clone()).await?;
+    Ok((volume, cover.into()))
+}
+
+/// Check that every page of the volume can be found within the archive,
+/// so that nothing is written to IndexedDB for an incomplete archive.
+fn validate(archive: &BlobZipArchive, pending: &PendingVolume) -> crate::Result<()> {
+    let PendingVolume { volume, pages } = pending;
+    if pages.is_empty() {
+        return Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::UnknownFormat));
+    }
+    if volume.page_index(volume.cover()).is_none() {
+        let cover = volume.cover().to_string();
+        return Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(cover)));
+    }
+    match pages.iter().find(|(entry, _)| !archive.contains(entry)) {
+        Some((entry, _)) => Err(AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(entry.clone()))),
+        None => Ok(())
+    }
+}
+
+/// Write the volume, its page images and OCR data into the mokuro IndexedDB.
+/// The import is all-or-nothing: if any page fails to be written, everything
+/// which has been written for the volume so far is deleted. The volume is marked
+/// as importing until every page has been written, in case the import is interrupted.
+pub async fn write_volume(
+    db: &Rc<Rexie>, archive: &BlobZipArchive, pending: PendingVolume,
+) -> crate::Result<(VolumeMetadata, gloo_file::ObjectUrl)> {
+    let (mut volume, cover_image) = write_importing_volume(db, archive, pending).await?;
+    finish_import(db, &mut volume, &cover_image).await?;
+    Ok((volume, cover_image.into()))
+}
+
+/// As `write_volume`, but the volume is left marked as importing (i.e. hidden from the
+/// library) until `finish_import` is called. Returns the volume and its cover image.
+pub async fn write_importing_volume(
+    db: &Rc<Rexie>, archive: &BlobZipArchive, pending: PendingVolume,
+) -> crate::Result<(VolumeMetadata, PageImage)> {
+    let PendingVolume { mut volume, pages } = pending;
+    volume.id = 0;  // ensure id is not specified. IndexDB determines this.
+    volume.importing = true;
+    volume.import_started = Some(timestamp());
+    volume.id = put_volume(db, &volume).await?;
+
+    match write_pages(db, archive, &mut volume, pages).await {
+        Ok(cover_image) => Ok((volume, cover_image)),
+        Err(error) => {
+            if let Err(rollback_error) = delete_volume(db, volume.id).await {
+                console::warn!(format!("failed to roll back import: {}", rollback_error));
+            }
+            Err(error)
+        }
+    }
+}
+
+/// Marks a volume written by `write_importing_volume` as imported, and creates its thumbnail.
+pub async fn finish_import(db: &Rc<Rexie>, volume: &mut VolumeMetadata, cover_image: &PageImage) -> crate::Result<()> {
+    volume.importing = false;
+    volume.import_started = None;
+    put_volume(db, volume).await?;
+    // The thumbnail is created when the library is next displayed if this fails.
+    if let Err(error) = write_thumbnail(db, volume.id, cover_image).await {
+        console::warn!(format!("failed to create thumbnail: {}", error));
+    }
+    Ok(())
+}
+
+/// Write the page images and OCR data of the volume into the mokuro IndexedDB.
+/// Each page is written in its own transaction, as reading the next page from the
+/// archive (or decoding the image) would otherwise end the transaction early.
+async fn write_pages(
+    db: &Rc<Rexie>, archive: &BlobZipArchive, volume: &mut VolumeMetadata, pages: Vec<(String, PageOcr)>,
+) -> crate::Result<PageImage> {
     let id = volume.id.into();
-    let (txn, pages_store, ocr_store) = start_bulk_write_txn(db)?;
-    for (page_name, ocr_name) in volume.pages.iter() {
-        let key = js_sys::Array::of2(&id, &page_name.as_str().into());
-        let image_data = {
-            let image_data = read_zipfile(&mut archive, page_name)?;
-            PageImage::new(page_name, &image_data[..])
-        };
-        pages_store.add(image_data.as_ref(), Some(&key)).await?;
+    let cover_index = volume.page_index(volume.cover()).unwrap_or_default();
+    let mut cover_image = None;
+    let mut wide_pages = vec![];
+    let names = volume.pages.iter().map(|(page_name, _)| page_name);
+    for (index, (page_name, (entry, mut ocr))) in names.zip(pages).enumerate() {
+        let page = async {
+            let key = js_sys::Array::of2(&id, &page_name.as_str().into());
+            let image_data = {
+                let image_data = archive.read(&entry).await?;
+                PageImage::new(page_name, &image_data[..])
+            };
+            // Pages without OCR take their dimensions from the image.
+            if ocr.img_width == 0 || ocr.img_height == 0 {
+                let blob: &gloo_file::Blob = image_data.as_ref();
+                (ocr.img_width, ocr.img_height) = get_image_dimensions(blob.as_ref()).await?;
+            }
 
-        let page_ocr = {
-            let ocr_data = read_zipfile(&mut archive, ocr_name)?;
-            let ocr = serde_json::from_slice::<PageOcr>(&ocr_data)?;
-            serde_wasm_bindgen::to_value(&ocr)?
+            let (txn, [pages_store, ocr_store, snapshot_store, search_store]) = start_bulk_write_txn(db)?;
+            let ocr_data = serde_to_wasm(&ocr)?;
+            pages_store.add(image_data.as_ref(), Some(&key)).await?;
+            ocr_store.add(&ocr_data, Some(&key)).await?;
+            snapshot_store.put(&ocr_data, Some(&key)).await?;
+            search_store.put(&search_entry(volume.id, page_name, &ocr)?, Some(&key)).await?;
+            txn.commit().await?;
+            Ok::<_, AppError>(image_data)
         };
-        ocr_store.add(&page_ocr, Some(&key)).await?;
+        let image_data = page.await.map_err(entry_error(&entry))?;
+
+        if ocr.is_wide() { wide_pages.push(index); }
+        if index == cover_index { cover_image = Some(image_data); }
     }
 
-    txn.commit().await?;
-    Ok((volume, cover_object_url))
+    volume.wide_pages = Some(wide_pages);
+    put_volume(db, volume).await?;
+    cover_image.ok_or_else(|| {
+        AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(volume.cover().to_string()))
+    })
+}
+
+/// Read and deserialize a JSON file from the archive.
+pub async fn read_json<T: DeserializeOwned>(archive: &BlobZipArchive, name: &str) -> crate::Result<T> {
+    let data = archive.read(name).await.map_err(entry_error(name))?;
+    serde_json::from_slice(&data).map_err(|error| entry_error(name)(error.into()))
 }
 
-/// construct a zip archive in memory from the volume data stored in the
-/// mokuro IndexedDB. The resultant gloo_file::File is a JS object that
+/// Attributes the error to the archive entry which caused it.
+pub fn entry_error(entry: &str) -> impl FnOnce(AppError) -> AppError + '_ {
+    move |error| match error {
+        AppError::ArchiveEntry(..) | AppError::InvalidMokuroFile(InvalidMokuroFileError::MissingFile(_)) => error,
+        _ => AppError::ArchiveEntry(entry.to_owned(), Box::new(error)),
+    }
+}
+
+fn archive_entries(archive: &BlobZipArchive) -> Vec<String> {
+    archive.file_names().map(String::from).collect()
+}
+
+/// construct a zip archive from the volume data stored in the mokuro IndexedDB.
+/// The archive is assembled page by page as a Blob, so only a single page is held
+/// in wasm memory at a time. The resultant gloo_file::File is a JS object that
 /// can then be downloaded through the browser.
+/// `progress` is called with (pages written, total pages) after each page, and
+/// the export stops (returning None) once `cancelled` is set.
 pub async fn create_ziparchive(
     db: Rc<Rexie>, volume_id: VolumeId,
-) -> crate::Result<gloo_file::File> {
+    progress: Callback<(usize, usize)>, cancelled: Rc<Cell<bool>>,
+) -> crate::Result<Option<gloo_file::File>> {
     let volume: VolumeMetadata = get_volume(&db, volume_id).await?;
 
-    let mut archive = ZipWriter::new(Cursor::new(vec![]));
-    let options = SimpleFileOptions::default()
-        .compression_method(zip::CompressionMethod::Stored);
-
+    let mut archive = BlobZipWriter::new();
     let metadata = {
         let mut volume = volume.clone();
         volume.id = 0;
         serde_json::to_vec(&volume)?
     };
-    write_zipfile(&mut archive, METADATA_FILE, &metadata, options)?;
-    archive.add_directory("_ocr/", options)?;
+    archive.add_file(METADATA_FILE, &metadata)?;
+    archive.add_directory("_ocr/")?;
 
     let id = volume.id.into();
-    for (page_name, ocr_name) in volume.pages.iter() {
+    let total = volume.pages.len();
+    for (index, (page_name, ocr_name)) in volume.pages.iter().enumerate() {
+        if cancelled.get() { return Ok(None); }
         let key = js_sys::Array::of2(&id, &page_name.as_str().into());
         let (image, ocr) = get_page_and_ocr(&db.clone(), &key.into()).await?;
 
         let image_data = gloo_file_read(image.as_ref()).await?;
-        write_zipfile(&mut archive, page_name, &image_data, options)?;
+        archive.add_file(page_name, &image_data)?;
 
         let ocr_data = serde_json::to_vec(&ocr)?;
-        write_zipfile(&mut archive, ocr_name, &ocr_data, options)?;
+        archive.add_file(ocr_name, &ocr_data)?;
+        if !cancelled.get() { progress.emit((index + 1, total)); }
     }
 
     let name = &format!("{}.mbz.zip", volume.title);
-    let buffer = archive.finish()?.into_inner();
-    Ok(gloo_file::File::new(name, &buffer[..]))
-}
-
-fn read_zipfile<R: Read + Seek>(
-    archive: &mut ZipArchive<R>,
-    name: &str,
-) -> crate::Result<Vec<u8>> {
-    // construct the ZipFile object.
-    let mut file = archive.by_name(name).map_err(|err| {
-        if let ZipError::FileNotFound = err {
-            return crate::errors::AppError::InvalidMokuroFile(
-                crate::errors::InvalidMokuroFileError::MissingFile(name.to_string())
-            );
-        }
-        err.into()
-    })?;
-
-    // copy the contents of the ZipFile into a Vec<u8>
-    let mut buffer = Vec::with_capacity(file.size() as usize);
-    std::io::copy(&mut file, &mut buffer).map_err(ZipError::Io)?;
-    Ok(buffer)
-}
-
-fn write_zipfile<W: Write + Seek>(
-    writer: &mut ZipWriter<W>,
-    name: &str,
-    content: &[u8],
-    options: SimpleFileOptions,
-) -> zip::result::ZipResult<usize> {
-    writer.start_file(name, options)?;
-    let mut bytes_written = 0;
-    while bytes_written < content.len() {
-        bytes_written += writer.write(&content[bytes_written..])?;
-    }
-    Ok(bytes_written)
+    Ok(Some(archive.finish(name)?))
 }
 
 // pub fn get_zipfile<'z, R: Read + Seek>(